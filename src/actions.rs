@@ -23,30 +23,268 @@
 use std::{
     path::{Path, PathBuf},
     process::Stdio,
+    time::{Duration, Instant},
 };
 
+use octocrab::params;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncWriteExt, process::Command};
+use tokio::process::Command;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+use crate::{
+    crates_io::CratesIoAPIQuery,
+    github,
+    steps::{EditedFiles, FileEdit, Step},
+    utilities::shell,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Plan {
+    /// Version of the plan file format, see [`Plan::FORMAT_VERSION`].
+    ///
+    /// Plans written before the format was versioned do not have this field
+    /// and are treated as version `0`.
+    #[serde(default)]
+    pub format_version: u32,
     pub items: Vec<PlanItem>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl Plan {
+    /// Current version of the plan file format.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Create a plan in the current format version.
+    pub fn new(items: Vec<PlanItem>) -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            items,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PlanItem {
+    /// Unique identifier other items can refer to in their `depends_on` list.
+    #[serde(default)]
+    pub id: Option<String>,
     /// Human readable description of item.
     #[serde(default)]
     pub description: Option<String>,
+    /// Identifiers of items that must succeed before this one is executed.
+    ///
+    /// If not set, the item depends on every item preceding it in the plan,
+    /// i.e. the plan is executed strictly in order.
+    #[serde(default)]
+    pub depends_on: Option<Vec<String>>,
+    /// Conditions that must hold before the action is executed.
+    #[serde(default)]
+    pub preconditions: Vec<Condition>,
     pub action: Action,
+    /// Conditions that must hold after the action has been executed.
+    #[serde(default)]
+    pub postconditions: Vec<Condition>,
     /// Report any errors but do not halt plan execution.
     #[serde(default)]
     pub continue_on_error: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl PlanItem {
+    /// Name of item suitable for log messages, `index` is its position in the
+    /// plan.
+    pub fn label(&self, index: usize) -> String {
+        match (self.id.as_ref(), self.description.as_ref()) {
+            (Some(id), Some(description)) => format!("`{id}` ({description})"),
+            (Some(id), None) => format!("`{id}`"),
+            (None, Some(description)) => format!("#{index} ({description})"),
+            (None, None) => format!("#{index} ({:?})", self.action),
+        }
+    }
+
+    /// Check `conditions` against the item's repository, returning the first
+    /// one that does not hold.
+    pub async fn first_unmet<'c>(
+        &self,
+        conditions: &'c [Condition],
+        context: &mut crate::Context,
+    ) -> Result<Option<&'c Condition>, Box<dyn std::error::Error>> {
+        for condition in conditions {
+            if !condition.check(self.action.repository(), context).await? {
+                return Ok(Some(condition));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Shell script lines performing the item's action, guarded by its pre-
+    /// and postconditions.
+    pub fn as_shell_command(
+        &self,
+        index: usize,
+        edited_files: &mut EditedFiles,
+        context: &mut crate::Context,
+    ) -> Option<Vec<String>> {
+        let cmds = self
+            .action
+            .as_shell_command(self.continue_on_error, edited_files, context)?;
+        if self.preconditions.is_empty() && self.postconditions.is_empty() {
+            return Some(cmds);
+        }
+        let on_failure = if self.continue_on_error {
+            ""
+        } else {
+            " exit 1;"
+        };
+        let label = self.label(index);
+        let guard = |conditions: &[Condition]| {
+            conditions
+                .iter()
+                .map(Condition::as_shell_test)
+                .collect::<Vec<_>>()
+                .join(" && ")
+        };
+        let indent = if self.preconditions.is_empty() {
+            ""
+        } else {
+            "    "
+        };
+        let cd = shell::cd(self.action.repository())?;
+        let mut lines = vec![cd.clone()];
+        if !self.preconditions.is_empty() {
+            lines.push(format!("if {}; then", guard(&self.preconditions)));
+        }
+        lines.extend(
+            cmds.into_iter()
+                .filter(|cmd| cmd != &cd)
+                .map(|cmd| format!("{indent}{cmd}")),
+        );
+        if !self.postconditions.is_empty() {
+            lines.push(format!(
+                "{indent}if ! {{ {}; }}; then echo {} >&2;{on_failure} fi",
+                guard(&self.postconditions),
+                shell::quote(&format!("Postconditions of {label} not met"))
+            ));
+        }
+        if self.preconditions.is_empty() {
+            return Some(lines);
+        }
+        lines.push("else".to_string());
+        lines.push(format!(
+            "    echo {} >&2;{on_failure}",
+            shell::quote(&format!("Preconditions of {label} not met"))
+        ));
+        lines.push("fi".to_string());
+        Some(lines)
+    }
+}
+
+/// Assertion about the state of a repository or <crates.io>, checked before
+/// or after a plan item's action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Condition {
+    /// Tag exists in the local repository, or in `remote` if given.
+    TagExists {
+        tag: String,
+        #[serde(default)]
+        remote: Option<String>,
+    },
+    /// Version of crate is published on <crates.io>.
+    CrateVersionPublished { crate_name: String, version: String },
+    /// Working tree has no uncommitted changes or untracked files.
+    WorkingTreeClean,
+    /// Negation of the inner condition.
+    Not(Box<Condition>),
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::TagExists { tag, remote: None } => write!(fmt, "tag `{tag}` exists"),
+            Self::TagExists {
+                tag,
+                remote: Some(remote),
+            } => write!(fmt, "tag `{tag}` exists in remote `{remote}`"),
+            Self::CrateVersionPublished {
+                crate_name,
+                version,
+            } => write!(fmt, "`{crate_name}` v{version} is published on crates.io"),
+            Self::WorkingTreeClean => write!(fmt, "working tree is clean"),
+            Self::Not(inner) => write!(fmt, "not ({inner})"),
+        }
+    }
+}
+
+impl Condition {
+    pub async fn check(
+        &self,
+        repository: &Path,
+        context: &mut crate::Context,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        match self {
+            Self::TagExists { tag, remote } => {
+                tag_exists(tag, remote.as_deref(), repository, context)
+            }
+            Self::CrateVersionPublished {
+                crate_name,
+                version,
+            } => {
+                let reply = crate::crates_io::get_version::Query {
+                    crate_name,
+                    version,
+                }
+                .get(context)
+                .await?;
+                log::debug!("get_version for {crate_name:?} API reply was: {reply:?}");
+                Ok(reply.is_ok())
+            }
+            Self::WorkingTreeClean => context.git().is_clean(repository),
+            Self::Not(inner) => Ok(!Box::pin(inner.check(repository, context)).await?),
+        }
+    }
+
+    /// Shell command that exits successfully if the condition holds, run
+    /// from inside the repository.
+    pub fn as_shell_test(&self) -> String {
+        match self {
+            Self::TagExists { tag, remote: None } => format!(
+                "{} >/dev/null",
+                shell::join(&[
+                    "git",
+                    "rev-parse",
+                    "--quiet",
+                    "--verify",
+                    &format!("refs/tags/{tag}")
+                ])
+            ),
+            Self::TagExists {
+                tag,
+                remote: Some(remote),
+            } => format!(
+                "{} >/dev/null",
+                shell::join(&[
+                    "git",
+                    "ls-remote",
+                    "--exit-code",
+                    remote,
+                    &format!("refs/tags/{tag}")
+                ])
+            ),
+            Self::CrateVersionPublished {
+                crate_name,
+                version,
+            } => format!(
+                "{} >/dev/null",
+                shell::join(&crate_version_argv(crate_name, version))
+            ),
+            Self::WorkingTreeClean => "[ -z \"$(git status --porcelain)\" ]".to_string(),
+            Self::Not(inner) => format!("! {{ {}; }}", inner.as_shell_test()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum Action {
     /// Interact with <crates.io> HTTP API.
@@ -73,37 +311,166 @@ pub enum Action {
 }
 
 impl Action {
+    /// Local checkout of repository the action refers to.
+    pub fn repository(&self) -> &Path {
+        match self {
+            Self::CratesIO { repository, .. }
+            | Self::Github { repository, .. }
+            | Self::Local { repository, .. } => repository,
+        }
+    }
+
+    /// External commands this action consists of.
+    pub fn steps(&self, context: &mut crate::Context) -> Vec<Step> {
+        match self {
+            Self::CratesIO { inner, .. } => inner.steps(context),
+            Self::Github { inner, .. } => inner.steps(context),
+            Self::Local { inner, .. } => inner.steps(context),
+        }
+    }
+
     pub async fn execute(
         &self,
         context: &mut crate::Context,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        if context.github_backend() == github::Backend::Api {
+            match self {
+                Self::Github { repository, inner } => {
+                    return inner.execute_api(repository, context).await;
+                }
+                Self::Local {
+                    repository,
+                    inner: LocalAction::AssertDefaultBranch,
+                } => {
+                    let client = github::Client::new(repository, context)?;
+                    let default_branch = client.default_branch().await?;
+                    let current_branch = context.git().current_branch(repository)?;
+                    if current_branch.as_ref() != Some(&default_branch) {
+                        return Err(format!(
+                            "Currently checked out branch {} is not the default branch \
+                             (`{default_branch}`), please check it out.",
+                            current_branch.as_deref().unwrap_or("(detached HEAD)")
+                        )
+                        .into());
+                    }
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        if let Self::CratesIO {
+            inner:
+                CratesIOAction::WaitForVersion {
+                    crate_name,
+                    version,
+                    timeout,
+                },
+            ..
+        } = self
+        {
+            return wait_for_version(crate_name, version, *timeout, context).await;
+        }
+        // Only one action may talk to crates.io at a time.
+        let _crates_io_call = match self {
+            Self::CratesIO {
+                inner: CratesIOAction::Publish { dry_run: true, .. },
+                ..
+            }
+            | Self::Github { .. }
+            | Self::Local { .. } => None,
+            Self::CratesIO { .. } => Some(context.crates_io_call().await),
+        };
+        for step in self.steps(context) {
+            step.execute(self.repository()).await?;
+        }
+        Ok(())
+    }
+
+    /// Check whether the effect of this action is already in place, e.g. after
+    /// an interrupted plan execution.
+    ///
+    /// Returns `None` for actions that can safely be executed again.
+    pub async fn is_done(
+        &self,
+        context: &mut crate::Context,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
         match self {
-            Self::CratesIO { repository, inner } => inner.execute(repository, context).await,
-            Self::Github { repository, inner } => inner.execute(repository, context).await,
-            Self::Local { repository, inner } => inner.execute(repository, context).await,
+            Self::CratesIO { repository, inner } => inner.is_done(repository, context).await,
+            Self::Github { repository, inner } => inner.is_done(repository, context).await,
+            Self::Local { repository, inner } => inner.is_done(repository, context).await,
+        }
+    }
+
+    /// Action that reverts the effect of this one, used when rolling back a
+    /// plan execution.
+    pub fn compensation(&self) -> Compensation {
+        let (repository, inner) = match self {
+            Self::CratesIO { repository, inner } => (repository, inner.compensation()),
+            Self::Github { repository, inner } => (repository, inner.compensation()),
+            Self::Local { repository, inner } => (repository, inner.compensation()),
+        };
+        match inner {
+            Compensation::None => Compensation::None,
+            Compensation::Irreversible => Compensation::Irreversible,
+            Compensation::Action(inner) => Compensation::Action(inner.with_repository(repository)),
         }
     }
 
+    /// Render as shell script lines, or `None` if the repository path or a
+    /// file edit can not be expressed in the script.
     pub fn as_shell_command(
         &self,
         continue_on_error: bool,
+        edited_files: &mut EditedFiles,
         context: &mut crate::Context,
     ) -> Option<Vec<String>> {
+        let steps = self.steps(context);
+        if steps.is_empty() {
+            return Some(vec![]);
+        }
+        let mut cmds = vec![shell::cd(self.repository())?];
+        for step in steps {
+            cmds.extend(step.as_shell_command(
+                self.repository(),
+                continue_on_error,
+                edited_files,
+            )?);
+        }
+        Some(cmds)
+    }
+}
+
+/// How to revert the effect of an action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Compensation<A = Action> {
+    /// The action has no lasting effect, e.g. it only checks something.
+    None,
+    /// Execute this action to revert the effect.
+    Action(A),
+    /// The effect can not be reverted, e.g. publishing a crate.
+    Irreversible,
+}
+
+/// An action not yet bound to a repository, see [`Action::compensation`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum InnerAction {
+    CratesIO(CratesIOAction),
+    Github(GithubAction),
+    Local(LocalAction),
+}
+
+impl InnerAction {
+    pub fn with_repository(self, repository: &Path) -> Action {
+        let repository = repository.to_path_buf();
         match self {
-            Self::CratesIO { repository, inner } => {
-                inner.as_shell_command(continue_on_error, repository, context)
-            }
-            Self::Github { repository, inner } => {
-                inner.as_shell_command(continue_on_error, repository, context)
-            }
-            Self::Local { repository, inner } => {
-                inner.as_shell_command(continue_on_error, repository, context)
-            }
+            Self::CratesIO(inner) => Action::CratesIO { repository, inner },
+            Self::Github(inner) => Action::Github { repository, inner },
+            Self::Local(inner) => Action::Local { repository, inner },
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum CratesIOAction {
     AddOwner {
@@ -118,275 +485,450 @@ pub enum CratesIOAction {
         crate_name: String,
         dry_run: bool,
     },
+    /// Wait until `version` of crate is visible on <crates.io>, e.g. before
+    /// publishing crates that depend on it.
+    ///
+    /// Checks are repeated with exponential backoff, starting at
+    /// [`CratesIOAction::WAIT_INTERVAL`] seconds.
+    WaitForVersion {
+        crate_name: String,
+        version: String,
+        /// Seconds after which to give up.
+        #[serde(default = "CratesIOAction::default_wait_timeout")]
+        timeout: u64,
+    },
 }
 
 impl CratesIOAction {
-    pub async fn execute(
-        &self,
-        repository: &Path,
-        context: &mut crate::Context,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        std::env::set_current_dir(repository)?;
+    /// Seconds before the first repeated check of
+    /// [`CratesIOAction::WaitForVersion`].
+    pub const WAIT_INTERVAL: u64 = 5;
+    /// Upper bound of the time between checks.
+    pub const WAIT_MAX_INTERVAL: u64 = 60;
+    /// Default timeout of [`CratesIOAction::WaitForVersion`] in seconds.
+    pub const WAIT_TIMEOUT: u64 = 600;
+
+    fn default_wait_timeout() -> u64 {
+        Self::WAIT_TIMEOUT
+    }
+}
+
+impl CratesIOAction {
+    pub fn steps(&self, context: &mut crate::Context) -> Vec<Step> {
+        let cargo_bin = Path::new(&context.cargo_bin())
+            .to_string_lossy()
+            .into_owned();
         match self {
             Self::AddOwner {
                 crate_name,
                 login_value,
-            } => {
-                log::info!("Adding `{login_value}` as owner of crate `{crate_name}`");
-
-                let output = Command::new(context.cargo_bin())
-                    .arg("owner")
-                    .arg("--add")
-                    .arg(login_value)
-                    .arg(crate_name)
-                    .stdin(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .output();
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!(
-                        "`cargo owner --add {login_value} {crate_name}` failed: {output:?}"
-                    )
-                    .into());
-                }
-                log::info!("OK: Added `{login_value}` as owner of crate `{crate_name}`");
-            }
+            } => vec![Step::run([
+                &cargo_bin,
+                "owner",
+                "--add",
+                login_value,
+                crate_name,
+            ])],
             Self::RemoveOwner {
                 crate_name,
                 login_value,
-            } => {
-                log::info!("Removing `{login_value}` as owner of crate `{crate_name}`");
-
-                let output = Command::new(context.cargo_bin())
-                    .arg("owner")
-                    .arg("--remove")
-                    .arg(login_value)
-                    .arg(crate_name)
-                    .stdin(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .output();
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!(
-                        "`cargo owner --remove {login_value} {crate_name}` failed: {output:?}"
-                    )
-                    .into());
-                }
-                log::info!("OK: Removed `{login_value}` as owner of crate `{crate_name}`");
-            }
+            } => vec![Step::run([
+                &cargo_bin,
+                "owner",
+                "--remove",
+                login_value,
+                crate_name,
+            ])],
             Self::Publish {
                 crate_name,
                 dry_run,
             } => {
-                log::info!("Publishing `{crate_name}` [dry_run={dry_run:?}]");
-
-                let mut command = Command::new(context.cargo_bin());
-                command.arg("publish").arg("--package").arg(crate_name);
+                let mut argv = vec![cargo_bin.as_str(), "publish"];
                 if *dry_run {
-                    command.arg("--dry-run");
+                    argv.push("--dry-run");
                 }
-                let output = command
-                    .stdin(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .output();
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!(
-                        "`cargo publish --package {crate_name}` failed: {output:?}"
-                    )
-                    .into());
-                }
-                log::info!("OK: Publishing `{crate_name}` [dry_run={dry_run:?}]");
+                argv.extend(["--package", crate_name]);
+                vec![Step::run(argv)]
             }
+            Self::WaitForVersion {
+                crate_name,
+                version,
+                timeout,
+            } => vec![Step::Poll {
+                argv: crate_version_argv(crate_name, version),
+                interval: Self::WAIT_INTERVAL,
+                max_interval: Self::WAIT_MAX_INTERVAL,
+                timeout: *timeout,
+                message: format!("Timed out waiting for `{crate_name}` v{version} on crates.io"),
+            }],
         }
-        Ok(())
     }
 
-    pub fn as_shell_command(
-        &self,
-        continue_on_error: bool,
-        repository: &Path,
-        context: &mut crate::Context,
-    ) -> Option<Vec<String>> {
-        let cargo_bin = context.cargo_bin();
-        let cargo_bin = Path::new(&cargo_bin);
+    pub fn compensation(&self) -> Compensation<InnerAction> {
         match self {
             Self::AddOwner {
                 crate_name,
                 login_value,
-            } => Some(vec![
-                format!("cd {} || exit 1", repository.display()),
-                format!(
-                    "{} owner --add {login_value} {crate_name}{continue_on_error}",
-                    cargo_bin.display(),
-                    continue_on_error = if continue_on_error {
-                        " || true"
-                    } else {
-                        " || exit 1"
-                    }
-                ),
-            ]),
+            } => Compensation::Action(InnerAction::CratesIO(Self::RemoveOwner {
+                crate_name: crate_name.clone(),
+                login_value: login_value.clone(),
+            })),
             Self::RemoveOwner {
                 crate_name,
                 login_value,
-            } => Some(vec![
-                format!("cd {} || exit 1", repository.display()),
-                format!(
-                    "{} owner --remove {login_value} {crate_name}{continue_on_error}",
-                    cargo_bin.display(),
-                    continue_on_error = if continue_on_error {
-                        " || true"
-                    } else {
-                        " || exit 1"
-                    }
-                ),
-            ]),
+            } => Compensation::Action(InnerAction::CratesIO(Self::AddOwner {
+                crate_name: crate_name.clone(),
+                login_value: login_value.clone(),
+            })),
+            Self::Publish { dry_run: true, .. } | Self::WaitForVersion { .. } => Compensation::None,
+            Self::Publish { dry_run: false, .. } => Compensation::Irreversible,
+        }
+    }
+
+    pub async fn is_done(
+        &self,
+        repository: &Path,
+        context: &mut crate::Context,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        match self {
+            Self::AddOwner { .. }
+            | Self::RemoveOwner { .. }
+            | Self::Publish { dry_run: true, .. }
+            | Self::WaitForVersion { .. } => Ok(None),
             Self::Publish {
                 crate_name,
-                dry_run,
-            } => Some(vec![
-                format!("cd {} || exit 1", repository.display()),
-                format!(
-                    "{} publish{dry_run} --package {crate_name}{continue_on_error}",
-                    cargo_bin.display(),
-                    dry_run = if *dry_run { " --dry-run" } else { "" },
-                    continue_on_error = if continue_on_error {
-                        " || true"
-                    } else {
-                        " || exit 1"
-                    }
-                ),
-            ]),
+                dry_run: false,
+            } => {
+                // `cargo metadata` is slow, keep it off the executor.
+                let c = {
+                    let (crate_name, repository, context) = (
+                        crate_name.clone(),
+                        repository.to_path_buf(),
+                        context.clone(),
+                    );
+                    tokio::task::spawn_blocking(move || {
+                        crate::utilities::repository::from_dir(&crate_name, &repository, &context)
+                            .map_err(|err| err.to_string())
+                    })
+                    .await??
+                };
+                let reply = crate::crates_io::get_version::Query {
+                    crate_name,
+                    version: &c.package.version,
+                }
+                .get(context)
+                .await?;
+                log::debug!("get_version for {crate_name:?} API reply was: {reply:?}");
+                Ok(Some(reply.is_ok()))
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum GithubAction {
+    /// Open a PR from the checked out branch.
     CreateReleasePR {
         crate_name: String,
         new_version: String,
+        /// Description of the PR, e.g. the release's changelog section.
+        #[serde(default)]
+        body: Option<String>,
+        /// Branch to open the PR from, as `owner:branch`. Defaults to the
+        /// checked out branch in the repository of the `origin` remote.
+        #[serde(default)]
+        head: Option<String>,
     },
     CreateRelease {
         crate_name: String,
         tag: String,
         version: String,
+        /// Release description, e.g. the version's changelog section.
+        /// Defaults to the tag message.
+        #[serde(default)]
+        notes: Option<String>,
+    },
+    /// Close the PR opened by `CreateReleasePR`.
+    CloseReleasePR {
+        crate_name: String,
+        new_version: String,
     },
+    /// Delete the release of `tag`, but not the tag itself.
+    DeleteRelease { tag: String },
 }
 
 impl GithubAction {
-    pub async fn execute(
+    pub fn steps(&self, context: &mut crate::Context) -> Vec<Step> {
+        let gh_bin = Path::new(&context.gh_bin()).to_string_lossy().into_owned();
+        match self {
+            Self::CreateReleasePR {
+                crate_name,
+                new_version,
+                body,
+                head,
+            } => {
+                let title = format!("Bump {crate_name} to v{new_version}");
+                let mut argv = vec![
+                    gh_bin.as_str(),
+                    "pr",
+                    "create",
+                    "--title",
+                    &title,
+                    "--body",
+                    body.as_deref().unwrap_or_default(),
+                ];
+                if let Some(head) = head {
+                    argv.extend(["--head", head]);
+                }
+                argv.extend(["--assignee", "@me"]);
+                vec![Step::run(argv)]
+            }
+            Self::CreateRelease {
+                crate_name,
+                tag,
+                version,
+                notes,
+            } => {
+                let title = format!("{crate_name}-v{version}");
+                let mut argv = vec![gh_bin.as_str(), "release", "create", tag, "--latest"];
+                match notes {
+                    Some(notes) => argv.extend(["--notes", notes]),
+                    None => argv.push("--notes-from-tag"),
+                }
+                argv.extend(["--verify-tag", "--title", &title]);
+                vec![Step::run(argv)]
+            }
+            Self::CloseReleasePR {
+                crate_name,
+                new_version,
+            } => {
+                let title = format!("Bump {crate_name} to v{new_version}");
+                vec![Step::ForEachLine {
+                    list: find_prs_argv(&gh_bin, &title, "open"),
+                    command: vec![gh_bin.clone(), "pr".into(), "close".into()],
+                }]
+            }
+            Self::DeleteRelease { tag } => {
+                vec![Step::run([&gh_bin, "release", "delete", tag, "--yes"])]
+            }
+        }
+    }
+
+    pub fn compensation(&self) -> Compensation<InnerAction> {
+        match self {
+            Self::CreateReleasePR {
+                crate_name,
+                new_version,
+                ..
+            } => Compensation::Action(InnerAction::Github(Self::CloseReleasePR {
+                crate_name: crate_name.clone(),
+                new_version: new_version.clone(),
+            })),
+            Self::CreateRelease { tag, .. } => {
+                Compensation::Action(InnerAction::Github(Self::DeleteRelease {
+                    tag: tag.clone(),
+                }))
+            }
+            // Reopening PRs or restoring deleted releases is not supported.
+            Self::CloseReleasePR { .. } | Self::DeleteRelease { .. } => Compensation::Irreversible,
+        }
+    }
+
+    /// Perform action with the GitHub REST API instead of `gh`.
+    pub async fn execute_api(
         &self,
         repository: &Path,
         context: &mut crate::Context,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        std::env::set_current_dir(repository)?;
+        let client = github::Client::new(repository, context)?;
         match self {
             Self::CreateReleasePR {
                 crate_name,
                 new_version,
+                body,
+                head,
             } => {
                 log::info!("Creating release PR for `{crate_name}` v{new_version}`");
-
                 let title = format!("Bump {crate_name} to v{new_version}");
-                let output = Command::new(context.gh_bin())
-                    .arg("pr")
-                    .arg("create")
-                    .arg("--title")
-                    .arg(title)
-                    .arg("--assignee")
-                    .arg("@me")
-                    .stdin(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .output();
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!("`gh pr create` failed: {output:?}").into());
-                }
-                log::info!("OK: Created PR");
+                let head = match head {
+                    Some(head) => head.clone(),
+                    None => {
+                        let Some(branch) = context.git().current_branch(repository)? else {
+                            return Err("Can not create PR from detached HEAD".into());
+                        };
+                        client.head(&branch)
+                    }
+                };
+                let number = client
+                    .create_pr(&title, &head, body.as_deref().unwrap_or_default())
+                    .await?;
+                log::info!("OK: Created PR #{number}");
             }
             Self::CreateRelease {
                 crate_name,
                 tag,
                 version,
+                notes,
             } => {
                 log::info!("Creating release for `{crate_name}` v{version}`");
                 let title = format!("{crate_name}-v{version}");
-                let output = Command::new(context.gh_bin())
-                    .arg("release")
-                    .arg("create")
-                    .arg(tag)
-                    .arg("--latest")
-                    .arg("--notes-from-tag")
-                    .arg("--verify-tag")
-                    .arg("--title")
-                    .arg(title)
-                    .stdin(Stdio::null())
-                    .stderr(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .output();
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!("`gh release create` failed: {output:?}").into());
-                }
+                let notes = match notes {
+                    Some(notes) => notes.clone(),
+                    None => context
+                        .git()
+                        .tag_message(repository, tag)?
+                        .unwrap_or_default(),
+                };
+                client.create_release(tag, &title, &notes).await?;
                 log::info!("OK: Created release");
             }
+            Self::CloseReleasePR {
+                crate_name,
+                new_version,
+            } => {
+                log::info!("Closing release PR for `{crate_name}` v{new_version}`");
+                let title = format!("Bump {crate_name} to v{new_version}");
+                for number in client.find_prs(&title, params::State::Open).await? {
+                    client.close_pr(number).await?;
+                    log::info!("OK: Closed PR #{number}");
+                }
+            }
+            Self::DeleteRelease { tag } => {
+                log::info!("Deleting release for tag `{tag}`");
+                client.delete_release(tag).await?;
+                log::info!("OK: Deleted release");
+            }
         }
         Ok(())
     }
 
-    pub fn as_shell_command(
+    pub async fn is_done(
         &self,
-        _continue_on_error: bool,
         repository: &Path,
         context: &mut crate::Context,
-    ) -> Option<Vec<String>> {
-        let gh_bin = context.gh_bin();
-        let gh_bin = Path::new(&gh_bin);
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        if context.github_backend() == github::Backend::Api {
+            let client = github::Client::new(repository, context)?;
+            return Ok(Some(match self {
+                Self::CreateReleasePR {
+                    crate_name,
+                    new_version,
+                    ..
+                } => {
+                    let title = format!("Bump {crate_name} to v{new_version}");
+                    !client
+                        .find_prs(&title, params::State::All)
+                        .await?
+                        .is_empty()
+                }
+                Self::CloseReleasePR {
+                    crate_name,
+                    new_version,
+                } => {
+                    let title = format!("Bump {crate_name} to v{new_version}");
+                    client
+                        .find_prs(&title, params::State::Open)
+                        .await?
+                        .is_empty()
+                }
+                Self::CreateRelease { tag, .. } => client.release_id(tag).await?.is_some(),
+                Self::DeleteRelease { tag } => client.release_id(tag).await?.is_none(),
+            }));
+        }
         match self {
             Self::CreateReleasePR {
                 crate_name,
                 new_version,
+                ..
             } => {
                 let title = format!("Bump {crate_name} to v{new_version}");
-                Some(vec![
-                    format!("cd {} || exit 1", repository.display()),
-                    format!(
-                        "{} pr create --title \"{title}\" --assignee \"@me\"",
-                        gh_bin.display()
-                    ),
-                ])
+                Ok(Some(
+                    !find_prs(&title, "all", repository, context)
+                        .await?
+                        .is_empty(),
+                ))
             }
-            Self::CreateRelease {
+            Self::CloseReleasePR {
                 crate_name,
-                tag,
-                version,
+                new_version,
             } => {
-                let title = format!("{crate_name}-v{version}");
-                Some(vec![
-                    format!("cd {} || exit 1", repository.display()),
-                    format!(
-                        "{} release create {tag} --latest --notes-from-tag --verify-tag --title \
-                         \"{title}\"",
-                        gh_bin.display()
-                    ),
-                ])
+                let title = format!("Bump {crate_name} to v{new_version}");
+                Ok(Some(
+                    find_prs(&title, "open", repository, context)
+                        .await?
+                        .is_empty(),
+                ))
+            }
+            Self::CreateRelease { tag, .. } | Self::DeleteRelease { tag } => {
+                let output = Command::new(context.gh_bin())
+                    .arg("release")
+                    .arg("view")
+                    .arg(tag)
+                    .current_dir(repository)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .output()
+                    .await
+                    .map_err(|err| format!("Could not run `gh release view`: {err}"))?;
+                let exists = output.status.success();
+                Ok(Some(exists == matches!(self, Self::CreateRelease { .. })))
             }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Command listing numbers of PRs in state `state` whose title contains
+/// `title`.
+fn find_prs_argv(gh_bin: &str, title: &str, state: &str) -> Vec<String> {
+    [
+        gh_bin,
+        "pr",
+        "list",
+        "--state",
+        state,
+        "--search",
+        &format!("\"{title}\" in:title"),
+        "--json",
+        "number",
+        "--jq",
+        ".[].number",
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+/// Numbers of PRs in state `state` whose title contains `title`.
+async fn find_prs(
+    title: &str,
+    state: &str,
+    repository: &Path,
+    context: &mut crate::Context,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let gh_bin = Path::new(&context.gh_bin()).to_string_lossy().into_owned();
+    let argv = find_prs_argv(&gh_bin, title, state);
+    let output = Command::new(&argv[0])
+        .args(&argv[1..])
+        .current_dir(repository)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .output()
+        .await
+        .map_err(|err| format!("Could not run `{}`: {err}", shell::join(&argv)))?;
+    if !output.status.success() {
+        return Err(format!("`gh pr list` failed: {output:?}").into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum LocalAction {
     /// Assert that current checkout is on the default branch.
@@ -397,187 +939,422 @@ pub enum LocalAction {
     // },
     CreateTags {
         new_tags: Vec<String>,
+        /// Annotation message of the tags, defaults to the tag name.
+        #[serde(default)]
+        message: Option<String>,
         // git_ref: Option<String>,
     },
     PublishTags {
         tags: Vec<String>,
         remote: Option<String>,
     },
-    // CommitVersionBump {
-    //     crate_name: String,
-    //     new_version: String,
-    // },
-    // CommitDependencyUpdate {
-    //     crate_name: String,
-    //     dependency_name: String,
-    //     new_version: String,
-    // },
+    DeleteTags {
+        tags: Vec<String>,
+    },
+    DeleteRemoteTags {
+        tags: Vec<String>,
+        remote: Option<String>,
+    },
+    /// Create branch `branch` from `base` and check it out.
+    CreateBranch {
+        branch: String,
+        base: String,
+    },
+    /// Check out `base` again and delete `branch`.
+    DeleteBranch {
+        branch: String,
+        base: String,
+    },
+    /// Set the version in the crate's manifest, release the unreleased section
+    /// of its changelog if given, and commit both with a `Signed-off-by`
+    /// trailer.
+    CommitVersionBump {
+        crate_name: String,
+        new_version: String,
+        /// Path of `Cargo.toml`, relative to the repository.
+        manifest: PathBuf,
+        /// Whether the crate inherits its version with `version.workspace =
+        /// true`, in which case `manifest` is the workspace root's and its
+        /// `[workspace.package].version` is set.
+        #[serde(default)]
+        workspace_version: bool,
+        /// Path of `CHANGELOG.md`, relative to the repository.
+        #[serde(default)]
+        changelog: Option<PathBuf>,
+        /// Release date written to the changelog, as `YYYY-MM-DD`.
+        date: String,
+    },
+    /// Push `branch` to `remote` and set it as its upstream.
+    PushBranch {
+        branch: String,
+        remote: String,
+    },
+    DeleteRemoteBranch {
+        branch: String,
+        remote: String,
+    },
+    /// Require `new_version` of path dependency `dependency_name` in the
+    /// crate's manifest and commit the change with a `Signed-off-by` trailer.
+    CommitDependencyUpdate {
+        crate_name: String,
+        dependency_name: String,
+        new_version: String,
+        /// Path of `Cargo.toml`, relative to the repository.
+        manifest: PathBuf,
+    },
 }
 
 impl LocalAction {
-    pub async fn execute(
-        &self,
-        repository: &Path,
-        context: &mut crate::Context,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        std::env::set_current_dir(repository)?;
+    pub fn steps(&self, context: &mut crate::Context) -> Vec<Step> {
         match self {
             Self::AssertDefaultBranch => {
-                let repo_name = String::from_utf8(
-                    Command::new(context.gh_bin())
-                        .args(["repo", "view", "--json", "name", "--jq", ".name"])
-                        .stdin(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .output()
-                        .await?
-                        .stdout,
-                )
-                .unwrap();
-                let repo_path = format!("repos/rust-vmm/{repo_name}");
-                let default_branch = String::from_utf8(
-                    Command::new(context.gh_bin())
-                        .args(["api", &repo_path, "--jq", ".default_branch"])
-                        .stdin(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .output()
-                        .await?
-                        .stdout,
-                )
-                .unwrap();
-                let current_branch = String::from_utf8(
-                    Command::new("git")
-                        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-                        .stdin(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .output()
-                        .await?
-                        .stdout,
-                )
-                .unwrap();
-                if current_branch != default_branch {
-                    return Err(format!(
-                        "Currently checked out branch {current_branch} is not the default branch \
-                         (`{default_branch}`), please check it out."
-                    )
-                    .into());
-                }
+                let gh_bin = Path::new(&context.gh_bin()).to_string_lossy().into_owned();
+                vec![Step::AssertSameOutput {
+                    left: vec![
+                        gh_bin,
+                        "repo".into(),
+                        "view".into(),
+                        "--json".into(),
+                        "defaultBranchRef".into(),
+                        "--jq".into(),
+                        ".defaultBranchRef.name".into(),
+                    ],
+                    right: vec![
+                        "git".into(),
+                        "rev-parse".into(),
+                        "--abbrev-ref".into(),
+                        "HEAD".into(),
+                    ],
+                    message: "Currently checked out branch is not the default branch, please \
+                              check it out."
+                        .into(),
+                }]
             }
-            Self::CreateTags { new_tags } => {
-                log::info!("Creating tags");
-                if new_tags.is_empty() {
-                    log::info!("No tags, doing nothing");
-                    return Ok(());
+            Self::CreateTags { new_tags, message } => new_tags
+                .iter()
+                .map(|tag| Step::Run {
+                    argv: vec![
+                        "git".into(),
+                        "tag".into(),
+                        "--annotate".into(),
+                        // Keep Markdown headings, which look like comments to git.
+                        "--cleanup=whitespace".into(),
+                        "--file".into(),
+                        "-".into(),
+                        tag.clone(),
+                    ],
+                    stdin: Some(message.clone().unwrap_or_else(|| tag.clone())),
+                })
+                .collect(),
+            Self::PublishTags { tags, remote } | Self::DeleteRemoteTags { tags, remote } => {
+                if tags.is_empty() {
+                    return vec![];
                 }
-                for tag in new_tags {
-                    let mut git_tag = Command::new("git")
-                        .arg("tag")
-                        .arg("--annotate")
-                        .arg("--file")
-                        .arg("-")
-                        .arg(tag)
-                        .stdin(Stdio::piped())
-                        .stderr(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .spawn()?;
-                    let mut stdin = git_tag.stdin.take().unwrap();
-                    stdin.write_all(tag.as_bytes()).await?;
-
-                    let output = git_tag.wait_with_output().await?;
-
-                    if !output.status.success() {
-                        return Err(format!("`git tag` failed: {output:?}").into());
-                    }
+                let mut argv = vec!["git".to_string(), "push".to_string()];
+                if matches!(self, Self::DeleteRemoteTags { .. }) {
+                    argv.push("--delete".into());
                 }
-                log::info!("OK: created tags");
+                argv.push(remote.as_deref().unwrap_or("upstream").into());
+                argv.extend(tags.iter().map(|tag| format!("refs/tags/{tag}")));
+                vec![Step::Run { argv, stdin: None }]
             }
-            Self::PublishTags { tags, remote } => {
-                let remote = remote.as_deref().unwrap_or("upstream");
-                log::info!("Pushing tags to remote {remote}");
+            Self::DeleteTags { tags } => {
                 if tags.is_empty() {
-                    log::info!("No tags, doing nothing");
-                    return Ok(());
+                    return vec![];
                 }
-                let output = {
-                    let mut cmd = Command::new("git");
-                    cmd.arg("push").arg(remote);
-                    for tag in tags {
-                        cmd.arg(tag);
-                    }
-                    cmd.stdin(Stdio::null())
-                        .stderr(Stdio::piped())
-                        .stdout(Stdio::piped())
-                        .output()
-                };
-                let output = output.await?;
-
-                if !output.status.success() {
-                    return Err(format!("`git push` failed: {output:?}").into());
+                vec![Step::run(
+                    ["git", "tag", "--delete"]
+                        .into_iter()
+                        .chain(tags.iter().map(String::as_str)),
+                )]
+            }
+            Self::CreateBranch { branch, base } => {
+                vec![Step::run(["git", "checkout", "-b", branch, base])]
+            }
+            Self::DeleteBranch { branch, base } => vec![
+                Step::run(["git", "checkout", base]),
+                Step::run(["git", "branch", "-D", branch]),
+            ],
+            Self::CommitVersionBump {
+                crate_name,
+                new_version,
+                manifest,
+                workspace_version,
+                changelog,
+                date,
+            } => {
+                let version = new_version.clone();
+                let mut steps = vec![Step::EditFile {
+                    path: manifest.clone(),
+                    edit: if *workspace_version {
+                        FileEdit::SetWorkspacePackageVersion { version }
+                    } else {
+                        FileEdit::SetPackageVersion { version }
+                    },
+                }];
+                let mut add = vec!["git".to_string(), "add".to_string()];
+                add.push(manifest.to_string_lossy().into_owned());
+                if let Some(changelog) = changelog {
+                    steps.push(Step::EditFile {
+                        path: changelog.clone(),
+                        edit: FileEdit::ReleaseChangelog {
+                            version: new_version.clone(),
+                            date: date.clone(),
+                        },
+                    });
+                    add.push(changelog.to_string_lossy().into_owned());
                 }
-                log::info!("OK: Pushed tags to remote {remote:?}");
+                steps.push(Step::Run {
+                    argv: add,
+                    stdin: None,
+                });
+                steps.push(Step::run([
+                    "git",
+                    "commit",
+                    "--signoff",
+                    "--message",
+                    &format!("Bump {crate_name} to v{new_version}"),
+                ]));
+                steps
+            }
+            Self::CommitDependencyUpdate {
+                crate_name,
+                dependency_name,
+                new_version,
+                manifest,
+            } => vec![
+                Step::EditFile {
+                    path: manifest.clone(),
+                    edit: FileEdit::SetDependencyVersion {
+                        dependency: dependency_name.clone(),
+                        version: new_version.clone(),
+                    },
+                },
+                Step::run(["git", "add", &manifest.to_string_lossy()]),
+                Step::run([
+                    "git",
+                    "commit",
+                    "--signoff",
+                    "--message",
+                    &format!("{crate_name}: Update {dependency_name} to v{new_version}"),
+                ]),
+            ],
+            Self::PushBranch { branch, remote } => {
+                vec![Step::run(["git", "push", "--set-upstream", remote, branch])]
+            }
+            Self::DeleteRemoteBranch { branch, remote } => {
+                vec![Step::run(["git", "push", "--delete", remote, branch])]
             }
         }
-        Ok(())
     }
 
-    pub fn as_shell_command(
+    pub fn compensation(&self) -> Compensation<InnerAction> {
+        match self {
+            Self::AssertDefaultBranch => Compensation::None,
+            Self::CreateTags { new_tags, .. } => {
+                Compensation::Action(InnerAction::Local(Self::DeleteTags {
+                    tags: new_tags.clone(),
+                }))
+            }
+            Self::PublishTags { tags, remote } => {
+                Compensation::Action(InnerAction::Local(Self::DeleteRemoteTags {
+                    tags: tags.clone(),
+                    remote: remote.clone(),
+                }))
+            }
+            Self::CreateBranch { branch, base } => {
+                Compensation::Action(InnerAction::Local(Self::DeleteBranch {
+                    branch: branch.clone(),
+                    base: base.clone(),
+                }))
+            }
+            // The commit is only on the release branch, which is deleted when rolling back its
+            // creation.
+            Self::CommitVersionBump { .. } | Self::CommitDependencyUpdate { .. } => {
+                Compensation::None
+            }
+            Self::PushBranch { branch, remote } => {
+                Compensation::Action(InnerAction::Local(Self::DeleteRemoteBranch {
+                    branch: branch.clone(),
+                    remote: remote.clone(),
+                }))
+            }
+            // The original tag messages and targets are not recorded anywhere.
+            Self::DeleteTags { .. } | Self::DeleteRemoteTags { .. } => Compensation::Irreversible,
+            Self::DeleteBranch { .. } | Self::DeleteRemoteBranch { .. } => {
+                Compensation::Irreversible
+            }
+        }
+    }
+
+    pub async fn is_done(
         &self,
-        continue_on_error: bool,
         repository: &Path,
-        _context: &mut crate::Context,
-    ) -> Option<Vec<String>> {
-        match self {
-            Self::AssertDefaultBranch => Some(vec![
-                format!("cd {} || exit 1", repository.display()),
-                "repo_name=$(gh repo view --json name --jq \".name\" || exit 1)".into(),
-                "default_branch=$(gh api \"repos/rust-vmm/${repo_name}\" --jq '.default_branch' \
-                 || exit 1)"
-                    .into(),
-                "current_branch=$(git rev-parse --abbrev-ref HEAD || exit 1)".into(),
-                "[ \"${default_branch}\" != \"${current_branch}\" ] && exit 1".into(),
-            ]),
-            // Self::AssertCargoMetadataVersion {
-            //     crate_name,
-            //     version,
-            // } => Some(vec![
-            //     format!("cd {} || exit 1", repository.display()),
-            //     format!(
-            //         "cargo_metadata_version=$(cargo metadata --format-version 1 --no-deps| jq
-            // '.packages[]|select(.name={crate_name})|.version')"     ),
-            //     format!("[ \"${{cargo_metadata_version}}\" != \"{version}\" ] && exit 1")
-            // ]),
-            Self::CreateTags { new_tags } => {
-                let mut cmds = vec![];
-                cmds.push(format!("cd {} || exit 1", repository.display()));
-                for tag in new_tags {
-                    cmds.push(format!("git tag -a {tag} || exit 1"));
-                }
-                Some(cmds)
+        context: &mut crate::Context,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let (tags, remote, should_exist) = match self {
+            Self::AssertDefaultBranch => return Ok(None),
+            Self::CreateBranch { branch, .. } => {
+                return Ok(Some(context.git().branch_exists(repository, branch)?));
+            }
+            Self::DeleteBranch { branch, .. } => {
+                return Ok(Some(!context.git().branch_exists(repository, branch)?));
+            }
+            Self::CommitVersionBump {
+                new_version,
+                manifest,
+                workspace_version,
+                ..
+            } => {
+                let contents = std::fs::read_to_string(repository.join(manifest))?;
+                let version = if *workspace_version {
+                    crate::manifest::workspace_package_version(&contents)?
+                } else {
+                    crate::manifest::package_version(&contents)?
+                };
+                return Ok(Some(
+                    &version == new_version && context.git().is_clean(repository)?,
+                ));
+            }
+            Self::CommitDependencyUpdate {
+                dependency_name,
+                new_version,
+                manifest,
+                ..
+            } => {
+                let contents = std::fs::read_to_string(repository.join(manifest))?;
+                let updated = crate::manifest::path_dependencies(&contents)?
+                    .into_iter()
+                    .filter(|dep| &dep.name == dependency_name)
+                    .filter(|dep| !dep.is_dev() || dep.version.is_some())
+                    .all(|dep| dep.version.as_ref() == Some(new_version));
+                return Ok(Some(updated && context.git().is_clean(repository)?));
+            }
+            Self::PushBranch { branch, remote } => {
+                return Ok(Some(
+                    context
+                        .git()
+                        .remote_branch_exists(repository, remote, branch)?,
+                ));
+            }
+            Self::DeleteRemoteBranch { branch, remote } => {
+                return Ok(Some(
+                    !context
+                        .git()
+                        .remote_branch_exists(repository, remote, branch)?,
+                ));
             }
+            Self::CreateTags { new_tags, .. } => (new_tags, None, true),
             Self::PublishTags { tags, remote } => {
-                if tags.is_empty() {
-                    return Some(vec![]);
-                }
-                Some(vec![
-                    format!("cd {} || exit 1", repository.display()),
-                    format!(
-                        "git push {remote} {tags}{continue_on_error}",
-                        remote = if let Some(remote) = remote {
-                            remote
-                        } else {
-                            "upstream"
-                        },
-                        tags = tags.join(" "),
-                        continue_on_error = if continue_on_error {
-                            " || true"
-                        } else {
-                            " || exit 1"
-                        }
-                    ),
-                ])
+                (tags, Some(remote.as_deref().unwrap_or("upstream")), true)
+            }
+            Self::DeleteTags { tags } => (tags, None, false),
+            Self::DeleteRemoteTags { tags, remote } => {
+                (tags, Some(remote.as_deref().unwrap_or("upstream")), false)
+            }
+        };
+        let mut existing = vec![];
+        for tag in tags {
+            if tag_exists(tag, remote, repository, context)? {
+                existing.push(tag.as_str());
+            }
+        }
+        if existing.is_empty() {
+            Ok(Some(!should_exist))
+        } else if existing.len() == tags.len() {
+            Ok(Some(should_exist))
+        } else {
+            Err(format!(
+                "Only some of the tags {tags:?} exist{}: {existing:?}, please resolve manually.",
+                remote
+                    .map(|r| format!(" in remote {r}"))
+                    .unwrap_or_default()
+            )
+            .into())
+        }
+    }
+}
+
+/// Check whether `tag` exists in `repository`, or in `remote` if given.
+fn tag_exists(
+    tag: &str,
+    remote: Option<&str>,
+    repository: &Path,
+    context: &crate::Context,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    match remote {
+        Some(remote) => context.git().remote_tag_exists(repository, remote, tag),
+        None => context.git().tag_exists(repository, tag),
+    }
+}
+
+/// Command that succeeds if `version` of crate is published on <crates.io>.
+fn crate_version_argv(crate_name: &str, version: &str) -> Vec<String> {
+    [
+        "curl",
+        "--silent",
+        "--fail",
+        "--user-agent",
+        "rust-vmm-helper-cli",
+        &format!("https://crates.io/api/v1/crates/{crate_name}/{version}"),
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+/// Poll <crates.io> until `version` of crate is visible, see
+/// [`CratesIOAction::WaitForVersion`].
+///
+/// Requests go through [`crate::Context::crates_io_call`] like all other API
+/// queries. Failed requests are retried like missing versions.
+async fn wait_for_version(
+    crate_name: &str,
+    version: &str,
+    timeout: u64,
+    context: &mut crate::Context,
+) -> Result<(), Box<dyn std::error::Error>> {
+    log::info!("Waiting for `{crate_name}` v{version} on crates.io");
+    let start = Instant::now();
+    let timeout = Duration::from_secs(timeout);
+    let mut interval = CratesIOAction::WAIT_INTERVAL;
+    loop {
+        let reply = crate::crates_io::get_version::Query {
+            crate_name,
+            version,
+        }
+        .get(context)
+        .await
+        // Request errors' `Display` includes a backtrace, only show the cause.
+        .map_err(|err| {
+            err.to_string()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        log::debug!("get_version for {crate_name:?} API reply was: {reply:?}");
+        match reply {
+            Ok(Ok(_)) => {
+                log::info!("OK: `{crate_name}` v{version} is on crates.io");
+                return Ok(());
             }
+            Ok(Err(_)) => {}
+            Err(err) => log::warn!("Could not query crates.io: {err}"),
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Err(format!(
+                "Timed out waiting for `{crate_name}` v{version} on crates.io after {}s",
+                elapsed.as_secs()
+            )
+            .into());
         }
+        let delay = Duration::from_secs(interval).min(timeout - elapsed);
+        log::info!(
+            "`{crate_name}` v{version} is not on crates.io yet, retrying in {}s",
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        interval = (interval * 2).min(CratesIOAction::WAIT_MAX_INTERVAL);
     }
 }
@@ -0,0 +1,446 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Parsing and editing of `CHANGELOG.md` files.
+//!
+//! rust-vmm crates keep changelogs in the [Keep a Changelog] style: one `##`
+//! heading per release, such as `## [v1.2.3] - 2025-01-31`, grouping changes
+//! under `###` headings such as `### Added` or `### Fixed`. Changes for the
+//! next release are collected under an `## Upcoming` (or `## [Unreleased]`)
+//! heading, which is renamed to the new version when releasing.
+//!
+//! [Keep a Changelog]: https://keepachangelog.com/en/1.1.0/
+
+use std::path::{Path, PathBuf};
+
+/// Titles of the section collecting unreleased changes.
+const UNRELEASED_TITLES: &[&str] = &[
+    "Upcoming",
+    "Upcoming Release",
+    "[Upcoming]",
+    "[Unreleased]",
+    "Unreleased",
+];
+
+/// Find the changelog of the crate in `crate_dir`, falling back to the
+/// repository root, since workspaces may keep a single changelog.
+pub fn find(crate_dir: &Path, repository: &Path) -> Option<PathBuf> {
+    [crate_dir, repository]
+        .into_iter()
+        .map(|dir| dir.join("CHANGELOG.md"))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changelog {
+    /// Releases in the order they appear in the file, i.e. newest first.
+    pub releases: Vec<Release>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// Version without `v` prefix, or `None` for the unreleased section.
+    pub version: Option<String>,
+    /// Release date as written in the heading.
+    pub date: Option<String>,
+    /// Text between the heading and the first section.
+    pub summary: String,
+    pub sections: Vec<Section>,
+    /// Index of the heading line in the file.
+    line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    /// Section text without heading, trimmed.
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionKind {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+    Other(String),
+}
+
+impl std::fmt::Display for SectionKind {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Added => write!(fmt, "Added"),
+            Self::Changed => write!(fmt, "Changed"),
+            Self::Deprecated => write!(fmt, "Deprecated"),
+            Self::Removed => write!(fmt, "Removed"),
+            Self::Fixed => write!(fmt, "Fixed"),
+            Self::Security => write!(fmt, "Security"),
+            Self::Other(title) => write!(fmt, "{title}"),
+        }
+    }
+}
+
+impl SectionKind {
+    fn from_title(title: &str) -> Self {
+        match title {
+            "Added" => Self::Added,
+            "Changed" => Self::Changed,
+            "Deprecated" => Self::Deprecated,
+            "Removed" => Self::Removed,
+            "Fixed" => Self::Fixed,
+            "Security" => Self::Security,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl Release {
+    /// Heading of the release, without `## `.
+    pub fn title(&self) -> String {
+        match (&self.version, &self.date) {
+            (None, _) => UNRELEASED_TITLES[0].to_string(),
+            (Some(version), None) => format!("[v{version}]"),
+            (Some(version), Some(date)) => format!("[v{version}] - {date}"),
+        }
+    }
+
+    /// Whether the release has neither a summary nor any changes.
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.sections.iter().all(|s| s.body.is_empty())
+    }
+
+    /// Release notes, i.e. the summary and all non-empty sections.
+    pub fn notes(&self) -> String {
+        let mut notes = vec![];
+        if !self.summary.is_empty() {
+            notes.push(self.summary.clone());
+        }
+        for section in &self.sections {
+            if !section.body.is_empty() {
+                notes.push(format!("### {}\n\n{}", section.kind, section.body));
+            }
+        }
+        notes.join("\n\n")
+    }
+}
+
+impl Changelog {
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut releases: Vec<Release> = vec![];
+        // Lines of the current section, or of the current release's summary.
+        let mut text: Vec<&str> = vec![];
+        let mut in_fence = false;
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            let heading = if in_fence {
+                None
+            } else if let Some(title) = line.strip_prefix("## ") {
+                Some((2, title.trim()))
+            } else if let Some(title) = line.strip_prefix("### ") {
+                Some((3, title.trim()))
+            } else if line.starts_with("# ") {
+                Some((1, ""))
+            } else {
+                None
+            };
+            let Some((level, title)) = heading else {
+                text.push(line);
+                continue;
+            };
+            if let Some(release) = releases.last_mut() {
+                Self::finish(release, &mut text);
+            } else {
+                // Preamble before the first release.
+                text.clear();
+            }
+            match level {
+                2 => releases.push(Self::parse_heading(title, i)?),
+                3 => {
+                    let Some(release) = releases.last_mut() else {
+                        return Err(format!(
+                            "Line {}: section `{title}` outside of a release",
+                            i + 1
+                        )
+                        .into());
+                    };
+                    release.sections.push(Section {
+                        kind: SectionKind::from_title(title),
+                        body: String::new(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        if let Some(release) = releases.last_mut() {
+            Self::finish(release, &mut text);
+        }
+        Ok(Self { releases })
+    }
+
+    /// Store the text collected since the last heading in `release`.
+    fn finish(release: &mut Release, text: &mut Vec<&str>) {
+        let body = text.join("\n").trim().to_string();
+        text.clear();
+        match release.sections.last_mut() {
+            Some(section) => section.body = body,
+            None => release.summary = body,
+        }
+    }
+
+    fn parse_heading(title: &str, line: usize) -> Result<Release, Box<dyn std::error::Error>> {
+        let release = |version, date| Release {
+            version,
+            date,
+            summary: String::new(),
+            sections: vec![],
+            line,
+        };
+        if UNRELEASED_TITLES
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(title))
+        {
+            return Ok(release(None, None));
+        }
+        let (version, rest) = match title.strip_prefix('[') {
+            Some(title) => title
+                .split_once(']')
+                .ok_or_else(|| format!("Line {}: unterminated `[` in `{title}`", line + 1))?,
+            None => title.split_once(' ').unwrap_or((title, "")),
+        };
+        let version = version.strip_prefix('v').unwrap_or(version);
+        if !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(format!("Line {}: `{title}` is not a release heading", line + 1).into());
+        }
+        let date = rest
+            .trim()
+            .trim_start_matches(['-', '(', ' '])
+            .trim_end_matches(')')
+            .trim();
+        Ok(release(
+            Some(version.to_string()),
+            (!date.is_empty()).then(|| date.to_string()),
+        ))
+    }
+
+    /// The section collecting unreleased changes, if any.
+    pub fn unreleased(&self) -> Option<&Release> {
+        self.releases.iter().find(|r| r.version.is_none())
+    }
+
+    /// The most recent released version.
+    pub fn latest(&self) -> Option<&Release> {
+        self.releases.iter().find(|r| r.version.is_some())
+    }
+
+    pub fn release(&self, version: &str) -> Option<&Release> {
+        self.releases
+            .iter()
+            .find(|r| r.version.as_deref() == Some(version))
+    }
+}
+
+/// Turn the unreleased section of changelog `contents` into the section of
+/// `version`, released on `date`, and start a new empty unreleased section
+/// above it.
+pub fn release(
+    contents: &str,
+    version: &str,
+    date: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let changelog = Changelog::parse(contents)?;
+    let Some(unreleased) = changelog.unreleased() else {
+        return Err(format!(
+            "Changelog has no unreleased section, expected a `## {}` heading",
+            UNRELEASED_TITLES[0]
+        )
+        .into());
+    };
+    if unreleased.is_empty() {
+        return Err("Unreleased section of changelog is empty".into());
+    }
+    if changelog.release(version).is_some() {
+        return Err(format!("Changelog already has a section for v{version}").into());
+    }
+    let released = Release {
+        version: Some(version.to_string()),
+        date: Some(date.to_string()),
+        ..unreleased.clone()
+    };
+    let mut lines = contents.lines().collect::<Vec<_>>();
+    let heading = format!("## {}", released.title());
+    let unreleased_heading = lines[unreleased.line];
+    lines.splice(
+        unreleased.line..=unreleased.line,
+        [unreleased_heading, "", &heading],
+    );
+    let mut released = lines.join("\n");
+    if contents.ends_with('\n') {
+        released.push('\n');
+    }
+    Ok(released)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "\
+# Changelog
+
+## [Unreleased]
+
+Summary of the next release.
+
+### Added
+
+- Example:
+
+```sh
+## not a heading
+```
+
+### Fixed
+
+## [v1.2.0] - 2025-01-31
+
+### Changed
+
+- Something
+
+## v1.1.0 (2024-12-01)
+
+## v1.0.0
+";
+
+    #[test]
+    fn test_parse() {
+        let changelog = Changelog::parse(CHANGELOG).unwrap();
+        let headings = changelog
+            .releases
+            .iter()
+            .map(|r| (r.version.as_deref(), r.date.as_deref(), r.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headings,
+            [
+                (None, None, 2),
+                (Some("1.2.0"), Some("2025-01-31"), 16),
+                (Some("1.1.0"), Some("2024-12-01"), 22),
+                (Some("1.0.0"), None, 24),
+            ]
+        );
+
+        let unreleased = changelog.unreleased().unwrap();
+        assert_eq!(unreleased.summary, "Summary of the next release.");
+        assert_eq!(
+            unreleased.sections,
+            [
+                Section {
+                    kind: SectionKind::Added,
+                    body: "- Example:\n\n```sh\n## not a heading\n```".to_string(),
+                },
+                Section {
+                    kind: SectionKind::Fixed,
+                    body: String::new(),
+                },
+            ]
+        );
+        assert!(!unreleased.is_empty());
+        assert_eq!(
+            unreleased.notes(),
+            "Summary of the next release.\n\n### Added\n\n- Example:\n\n```sh\n## not a \
+             heading\n```"
+        );
+        assert_eq!(changelog.latest().unwrap().title(), "[v1.2.0] - 2025-01-31");
+        assert!(changelog.release("1.1.0").unwrap().is_empty());
+
+        let changelog = Changelog::parse("## Upcoming\n\n### Added\n\n- New\n").unwrap();
+        assert_eq!(
+            changelog.unreleased().unwrap().notes(),
+            "### Added\n\n- New"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = Changelog::parse("# Changelog\n\n## Notes\n")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Line 3: `Notes` is not a release heading");
+
+        let err = Changelog::parse("## [v1.0.0 - 2025-01-31\n")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Line 1: unterminated `[` in `v1.0.0 - 2025-01-31`");
+
+        let err = Changelog::parse("### Added\n").unwrap_err().to_string();
+        assert_eq!(err, "Line 1: section `Added` outside of a release");
+    }
+
+    #[test]
+    fn test_release() {
+        let released = release(CHANGELOG, "1.3.0", "2025-02-28").unwrap();
+        assert_eq!(
+            released,
+            CHANGELOG.replace(
+                "## [Unreleased]\n",
+                "## [Unreleased]\n\n## [v1.3.0] - 2025-02-28\n"
+            )
+        );
+        let changelog = Changelog::parse(&released).unwrap();
+        assert!(changelog.unreleased().unwrap().is_empty());
+        assert_eq!(
+            changelog.release("1.3.0").unwrap().notes(),
+            Changelog::parse(CHANGELOG)
+                .unwrap()
+                .unreleased()
+                .unwrap()
+                .notes()
+        );
+
+        // `Upcoming` headings are kept as they are, as is a missing final newline.
+        assert_eq!(
+            release("## Upcoming\n\n- New", "0.2.0", "2025-02-28").unwrap(),
+            "## Upcoming\n\n## [v0.2.0] - 2025-02-28\n\n- New"
+        );
+
+        let err = release(CHANGELOG, "1.2.0", "2025-02-28")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Changelog already has a section for v1.2.0");
+        let err = release(&released, "1.4.0", "2025-02-28")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Unreleased section of changelog is empty");
+        let err = release("## v1.0.0\n", "1.1.0", "2025-02-28")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Changelog has no unreleased section, expected a `## Upcoming` heading"
+        );
+    }
+}
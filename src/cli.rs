@@ -22,7 +22,9 @@
 
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+use crate::{git, github};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -31,6 +33,14 @@ pub struct Cli {
     #[arg(long, value_name = "CARGO_BIN")]
     pub cargo_bin: Option<PathBuf>,
 
+    /// How to read local git repositories.
+    #[arg(long, value_enum, default_value_t = git::Backend::Native)]
+    pub git_backend: git::Backend,
+
+    /// How to perform GitHub actions.
+    #[arg(long, value_enum, default_value_t = github::Backend::Api)]
+    pub github_backend: github::Backend,
+
     /// Default command is `health-check`.
     #[command(subcommand)]
     pub command: Option<Command>,
@@ -52,6 +62,12 @@ pub enum Command {
         /// Check only one package in repository instead of all.
         #[arg(short, long, value_name = "NAME")]
         package: Option<String>,
+        /// Run only these checks, see `list-checks`.
+        #[arg(long, value_name = "ID", value_delimiter = ',')]
+        check: Vec<String>,
+        /// Do not run these checks.
+        #[arg(long, value_name = "ID", value_delimiter = ',')]
+        skip: Vec<String>,
         /// Attempt to fix automatically.
         #[arg(short, long, default_value = "false")]
         fix: bool,
@@ -59,6 +75,8 @@ pub enum Command {
         #[arg(short, long, value_name = "JSON_PLAN_FILE")]
         json_plan_output: Option<PathBuf>,
     },
+    /// Lists available health checks.
+    ListChecks,
     /// Attempts to generate an action plan into a JSON file.
     Action {
         /// Action to execute
@@ -79,12 +97,74 @@ pub enum Command {
         /// Output shell script instead of executing it.
         #[arg(short, long, value_name = "SHELLSCRIPT_OUTPUT_FILE")]
         shellscript_output: Option<PathBuf>,
-        /// Dry run (validate but do not actually perform anything).
+        /// Output GitHub Actions workflow instead of executing it.
+        #[arg(
+            short,
+            long,
+            value_name = "WORKFLOW_FILE",
+            conflicts_with = "shellscript_output"
+        )]
+        workflow_output: Option<PathBuf>,
+        /// Dry run (validate and print what would be done, but do not actually
+        /// perform anything).
         #[arg(short, long, default_value = "false")]
         dry_run: bool,
+        /// Ask for confirmation before executing each item.
+        #[arg(short, long, default_value = "false", conflicts_with_all = ["shellscript_output", "workflow_output", "dry_run"])]
+        interactive: bool,
+        /// Number of items without mutual dependencies to execute
+        /// concurrently.
+        #[arg(
+            long,
+            value_name = "N",
+            default_value = "1",
+            conflicts_with = "interactive"
+        )]
+        jobs: usize,
+        #[command(flatten)]
+        variables: PlanVariables,
+        /// Record execution progress to this file. Defaults to
+        /// `JSON_PLAN_FILE.journal` when the plan is read from a file.
+        #[arg(long, value_name = "JOURNAL_FILE")]
+        journal: Option<PathBuf>,
+        /// Resume an interrupted execution, skipping items the journal
+        /// records as completed.
+        #[arg(long, default_value = "false")]
+        resume: bool,
+        /// Revert the effects of the items the journal records as completed,
+        /// most recent first. With `--dry-run`, only report what would be
+        /// done.
+        #[arg(long, default_value = "false", conflicts_with_all = ["resume", "shellscript_output", "workflow_output"])]
+        rollback: bool,
+    },
+    /// Validates serialized action plan JSON without executing it.
+    ValidatePlan {
+        /// Read action plan from file instead of `STDIN`.
+        #[arg(short, long, value_name = "JSON_PLAN_FILE")]
+        json_plan_input: Option<PathBuf>,
+        #[command(flatten)]
+        variables: PlanVariables,
+    },
+    /// Prints the JSON Schema of action plan files.
+    PlanSchema {
+        /// Write schema to file instead of `STDOUT`.
+        #[arg(short, long, value_name = "JSON_SCHEMA_FILE")]
+        output: Option<PathBuf>,
     },
 }
 
+/// Values for `${name}` placeholders in action plans.
+#[derive(Args, Debug, Clone, Default)]
+pub struct PlanVariables {
+    /// Set plan variable, can be given multiple times. The value is a string,
+    /// or a boolean if it is `true` or `false`.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub set: Vec<String>,
+    /// Read plan variables from a TOML file; `--set` takes precedence.
+    #[arg(long, value_name = "VARS_FILE")]
+    pub vars: Option<PathBuf>,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum ActionCommand {
     /// Publish one or more crates to <crates.io>.
@@ -92,6 +172,20 @@ pub enum ActionCommand {
         /// Package(s) to publish
         package: Vec<String>,
     },
+    /// Open a release PR for a crate: bump its version, release its changelog
+    /// section and push a signed-off commit to a new branch in your fork.
+    PrepareRelease {
+        /// Package to release.
+        package: String,
+        /// Version to release.
+        version: String,
+        /// Remote of your fork, where the release branch is pushed.
+        #[arg(long, value_name = "REMOTE", default_value = "origin")]
+        fork: String,
+        /// Name of release branch, defaults to `release-PACKAGE-vVERSION`.
+        #[arg(long, value_name = "BRANCH")]
+        branch: Option<String>,
+    },
 }
 
 impl Default for Command {
@@ -100,6 +194,8 @@ impl Default for Command {
             repository: std::env::current_dir()
                 .expect("Command::default(): Could not access current process directory"),
             package: None,
+            check: vec![],
+            skip: vec![],
             fix: false,
             json_plan_output: None,
         }
@@ -35,15 +35,35 @@ lazy_static::lazy_static! {
     };
 }
 
-#[derive(Debug)]
-pub struct Config {}
+pub struct Config {
+    /// Token for the GitHub API, from `GITHUB_TOKEN` or `GH_TOKEN`.
+    github_token: Option<String>,
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field(
+                "github_token",
+                &self.github_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
 
 impl Config {
     pub fn check(&self) -> bool {
         true
     }
 
+    pub fn github_token(&self) -> Option<&str> {
+        self.github_token.as_deref()
+    }
+
     fn init() -> Result<Self, String> {
-        Ok(Self {})
+        let github_token = ["GITHUB_TOKEN", "GH_TOKEN"]
+            .into_iter()
+            .find_map(|var| std::env::var(var).ok().filter(|token| !token.is_empty()));
+        Ok(Self { github_token })
     }
 }
@@ -97,7 +97,7 @@ pub mod get_owners {
             let octocrab = octocrab::instance();
             let owners_endpoint =
                 format!("https://crates.io/api/v1/crates/{}/owners", self.crate_name);
-            context.crates_io_call().await;
+            let _crates_io_call = context.crates_io_call().await;
             let response = octocrab._get(&owners_endpoint).await?;
             log::debug!("response: {:?}", response);
             let (_parts, body) = response.into_parts();
@@ -187,7 +187,7 @@ pub mod get_crate {
                 "https://crates.io/api/v1/crates/{}?include=default_version",
                 self.crate_name
             );
-            context.crates_io_call().await;
+            let _crates_io_call = context.crates_io_call().await;
             let response = octocrab._get(&crate_endpoint).await?;
             log::debug!("response: {:?}", response);
             let (_parts, body) = response.into_parts();
@@ -200,6 +200,58 @@ pub mod get_crate {
     impl CratesIoAPIResponse for Response {}
 }
 
+pub mod get_version {
+    use super::*;
+
+    pub struct Query<'a> {
+        pub crate_name: &'a str,
+        pub version: &'a str,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Response {
+        pub version: Version,
+    }
+
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Version {
+        pub id: i64,
+        #[serde(rename = "crate")]
+        pub crate_field: String,
+        pub num: String,
+        pub yanked: bool,
+        #[serde(rename = "created_at")]
+        pub created_at: String,
+    }
+
+    #[async_trait]
+    impl<'a> CratesIoAPIQuery for Query<'a> {
+        type Response = Response;
+
+        async fn get(
+            &self,
+            context: &mut crate::Context,
+        ) -> Result<Result<Self::Response, CratesIoAPIError>, Box<dyn std::error::Error>> {
+            let octocrab = octocrab::instance();
+            let version_endpoint = format!(
+                "https://crates.io/api/v1/crates/{}/{}",
+                self.crate_name, self.version
+            );
+            let _crates_io_call = context.crates_io_call().await;
+            let response = octocrab._get(&version_endpoint).await?;
+            log::debug!("response: {:?}", response);
+            let (_parts, body) = response.into_parts();
+            let version_reply = body.collect().await?.to_bytes();
+            log::debug!("API reply was: {:?}", version_reply);
+            Self::Response::try_deserialize(&version_reply)
+        }
+    }
+
+    impl CratesIoAPIResponse for Response {}
+}
+
 // GET https://crates.io/api/v1/crates/libloading/reverse_dependencies?page=2&per_page=10
 pub mod reverse_dependencies {
     use super::*;
@@ -311,7 +363,7 @@ pub mod reverse_dependencies {
             loop {
                 let endpoint =
                     format!("https://crates.io/api/v1/crates/{}/reverse_dependencies?page={page}&per_page=10", self.crate_name);
-                context.crates_io_call().await;
+                let _crates_io_call = context.crates_io_call().await;
                 let response = octocrab._get(&endpoint).await?;
                 log::debug!("response: {response:?}");
                 let (_parts, body) = response.into_parts();
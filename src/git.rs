@@ -0,0 +1,443 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Read-only queries on local git repositories.
+//!
+//! [`Native`] reads repositories directly with `gix`, [`Cli`] runs the `git`
+//! binary and is kept as a fallback. Actions that modify repositories are
+//! described as [`crate::steps::Step`]s instead, so that they can be exported
+//! as shell scripts.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// A configured remote of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    /// Fetch URL, if configured.
+    pub url: Option<String>,
+}
+
+pub trait GitBackend: std::fmt::Debug + Send + Sync {
+    /// Short name of the checked out branch, or `None` if `HEAD` is detached.
+    fn current_branch(
+        &self,
+        repository: &Path,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    fn tag_exists(&self, repository: &Path, tag: &str) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Message of annotated tag `tag`, or `None` if it does not exist or is
+    /// a lightweight tag.
+    fn tag_message(
+        &self,
+        repository: &Path,
+        tag: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    /// Whether local branch `branch` exists.
+    fn branch_exists(
+        &self,
+        repository: &Path,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Whether there are no staged, unstaged or untracked changes.
+    fn is_clean(&self, repository: &Path) -> Result<bool, Box<dyn std::error::Error>>;
+
+    fn remotes(&self, repository: &Path) -> Result<Vec<Remote>, Box<dyn std::error::Error>>;
+
+    /// Whether `tag` exists in `remote`, which requires network access.
+    fn remote_tag_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        tag: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Whether branch `branch` exists in `remote`, which requires network
+    /// access.
+    fn remote_branch_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+}
+
+/// Backend selection, see [`crate::Context::set_git_backend`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Read repositories with `gix`.
+    #[default]
+    Native,
+    /// Run the `git` binary from `PATH`.
+    Cli,
+}
+
+impl Backend {
+    pub fn build(self) -> Box<dyn GitBackend> {
+        match self {
+            Self::Native => Box::new(Native),
+            Self::Cli => Box::new(Cli),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Native;
+
+impl Native {
+    fn open(repository: &Path) -> Result<gix::Repository, Box<dyn std::error::Error>> {
+        gix::open(repository).map_err(|err| {
+            format!(
+                "Could not open git repository `{}`: {err}",
+                repository.display()
+            )
+            .into()
+        })
+    }
+}
+
+impl GitBackend for Native {
+    fn current_branch(
+        &self,
+        repository: &Path,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        Ok(repo.head_name()?.map(|name| name.shorten().to_string()))
+    }
+
+    fn tag_exists(&self, repository: &Path, tag: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        Ok(repo
+            .try_find_reference(format!("refs/tags/{tag}").as_str())?
+            .is_some())
+    }
+
+    fn tag_message(
+        &self,
+        repository: &Path,
+        tag: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        let Some(reference) = repo.try_find_reference(format!("refs/tags/{tag}").as_str())? else {
+            return Ok(None);
+        };
+        let object = reference.id().object()?;
+        if object.kind != gix::object::Kind::Tag {
+            return Ok(None);
+        }
+        let tag = object.try_into_tag()?;
+        Ok(Some(
+            tag.decode()?.message.to_string().trim_end().to_string(),
+        ))
+    }
+
+    fn branch_exists(
+        &self,
+        repository: &Path,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        Ok(repo
+            .try_find_reference(format!("refs/heads/{branch}").as_str())?
+            .is_some())
+    }
+
+    fn is_clean(&self, repository: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        let mut changes = repo
+            .status(gix::progress::Discard)?
+            .untracked_files(gix::status::UntrackedFiles::Files)
+            .into_iter(None)?;
+        match changes.next() {
+            None => Ok(true),
+            Some(change) => {
+                log::debug!(
+                    "Working tree of `{}` has changes: {:?}",
+                    repository.display(),
+                    change?
+                );
+                Ok(false)
+            }
+        }
+    }
+
+    fn remotes(&self, repository: &Path) -> Result<Vec<Remote>, Box<dyn std::error::Error>> {
+        let repo = Self::open(repository)?;
+        let mut remotes = vec![];
+        for name in repo.remote_names() {
+            let remote = repo.find_remote(name.as_ref() as &gix::bstr::BStr)?;
+            remotes.push(Remote {
+                name: name.to_string(),
+                url: remote
+                    .url(gix::remote::Direction::Fetch)
+                    .map(|url| url.to_bstring().to_string()),
+            });
+        }
+        Ok(remotes)
+    }
+
+    /// Querying remotes is left to the `git` binary, which has the user's
+    /// credentials and transport configuration.
+    fn remote_tag_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        tag: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        Cli.remote_tag_exists(repository, remote, tag)
+    }
+
+    fn remote_branch_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        Cli.remote_branch_exists(repository, remote, branch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cli;
+
+impl Cli {
+    /// Run `git` in `repository`, returning the command's exit status and
+    /// standard output.
+    fn git(repository: &Path, args: &[&str]) -> Result<(bool, String), Box<dyn std::error::Error>> {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(repository)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped())
+            .output()
+            .map_err(|err| format!("Could not run `git {}`: {err}", args.join(" ")))?;
+        Ok((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ))
+    }
+
+    /// Like [`Cli::git`], but fails if the command fails.
+    fn git_stdout(repository: &Path, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+        match Self::git(repository, args)? {
+            (true, stdout) => Ok(stdout),
+            (false, _) => Err(format!("`git {}` failed", args.join(" ")).into()),
+        }
+    }
+}
+
+impl GitBackend for Cli {
+    fn current_branch(
+        &self,
+        repository: &Path,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match Self::git(repository, &["symbolic-ref", "--quiet", "--short", "HEAD"])? {
+            (true, stdout) => Ok(Some(stdout.trim().to_string())),
+            (false, _) => Ok(None),
+        }
+    }
+
+    fn tag_exists(&self, repository: &Path, tag: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let tag_ref = format!("refs/tags/{tag}");
+        Ok(Self::git(repository, &["rev-parse", "--quiet", "--verify", &tag_ref])?.0)
+    }
+
+    fn tag_message(
+        &self,
+        repository: &Path,
+        tag: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let tag_ref = format!("refs/tags/{tag}");
+        match Self::git(repository, &["cat-file", "-t", &tag_ref])? {
+            (true, kind) if kind.trim() == "tag" => {}
+            _ => return Ok(None),
+        }
+        // `%(contents:subject)` joins the lines of the first paragraph, so take
+        // the whole message and remove the signature of signed tags instead.
+        let contents = Self::git_stdout(
+            repository,
+            &["for-each-ref", "--format=%(contents)", &tag_ref],
+        )?;
+        let signature = Self::git_stdout(
+            repository,
+            &["for-each-ref", "--format=%(contents:signature)", &tag_ref],
+        )?;
+        let contents = contents.trim_end();
+        let message = contents
+            .strip_suffix(signature.trim_end())
+            .unwrap_or(contents);
+        Ok(Some(message.trim_end().to_string()))
+    }
+
+    fn branch_exists(
+        &self,
+        repository: &Path,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let branch_ref = format!("refs/heads/{branch}");
+        Ok(Self::git(
+            repository,
+            &["rev-parse", "--quiet", "--verify", &branch_ref],
+        )?
+        .0)
+    }
+
+    fn is_clean(&self, repository: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(Self::git_stdout(repository, &["status", "--porcelain"])?
+            .trim()
+            .is_empty())
+    }
+
+    fn remotes(&self, repository: &Path) -> Result<Vec<Remote>, Box<dyn std::error::Error>> {
+        let mut remotes = vec![];
+        for name in Self::git_stdout(repository, &["remote"])?.lines() {
+            let (found, url) = Self::git(repository, &["remote", "get-url", name])?;
+            remotes.push(Remote {
+                name: name.to_string(),
+                url: found.then(|| url.trim().to_string()),
+            });
+        }
+        Ok(remotes)
+    }
+
+    fn remote_tag_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        tag: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let tag_ref = format!("refs/tags/{tag}");
+        Ok(Self::git(repository, &["ls-remote", "--exit-code", remote, &tag_ref])?.0)
+    }
+
+    fn remote_branch_exists(
+        &self,
+        repository: &Path,
+        remote: &str,
+        branch: &str,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let branch_ref = format!("refs/heads/{branch}");
+        Ok(Self::git(
+            repository,
+            &["ls-remote", "--exit-code", remote, &branch_ref],
+        )?
+        .0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{git, repository};
+
+    /// Repository with one commit on `master`, an annotated and a lightweight
+    /// tag, and two remotes.
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path();
+        repository(repo, "master", &[("README.md", "# Fixture\n")]);
+        git(
+            repo,
+            &[
+                "tag",
+                "--annotate",
+                "--cleanup=whitespace",
+                "--message",
+                MARKDOWN_MESSAGE,
+                "v1.0.0",
+            ],
+        );
+        git(repo, &["tag", "lightweight"]);
+        git(
+            repo,
+            &["remote", "add", "upstream", "https://example.com/up.git"],
+        );
+        git(repo, &["remote", "add", "origin", "/path/with space/fork"]);
+        dir
+    }
+
+    const MARKDOWN_MESSAGE: &str = "# v1.0.0\nReleased today.\n\n## Added\n\n- `feature` for \
+                                    *everyone*\n\n```sh\ncargo add it\n```";
+
+    /// Call `f` with both backends and check that they agree.
+    fn same<T: PartialEq + std::fmt::Debug>(
+        f: impl Fn(&dyn GitBackend) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> T {
+        let native = f(&Native).unwrap();
+        let cli = f(&Cli).unwrap();
+        assert_eq!(native, cli, "native and cli backends disagree");
+        native
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let dir = fixture();
+        let repo = dir.path();
+
+        assert_eq!(same(|b| b.current_branch(repo)), Some("master".to_string()));
+        assert!(same(|b| b.branch_exists(repo, "master")));
+        assert!(!same(|b| b.branch_exists(repo, "v1.0.0")));
+
+        assert!(same(|b| b.tag_exists(repo, "v1.0.0")));
+        assert!(same(|b| b.tag_exists(repo, "lightweight")));
+        assert!(!same(|b| b.tag_exists(repo, "master")));
+        assert_eq!(
+            same(|b| b.tag_message(repo, "v1.0.0")),
+            Some(MARKDOWN_MESSAGE.to_string())
+        );
+        assert_eq!(same(|b| b.tag_message(repo, "lightweight")), None);
+        assert_eq!(same(|b| b.tag_message(repo, "v2.0.0")), None);
+
+        assert_eq!(
+            same(|b| b.remotes(repo)),
+            [
+                Remote {
+                    name: "origin".into(),
+                    url: Some("/path/with space/fork".into()),
+                },
+                Remote {
+                    name: "upstream".into(),
+                    url: Some("https://example.com/up.git".into()),
+                },
+            ]
+        );
+
+        assert!(same(|b| b.is_clean(repo)));
+        std::fs::write(repo.join("untracked.txt"), "").unwrap();
+        assert!(!same(|b| b.is_clean(repo)));
+        std::fs::remove_file(repo.join("untracked.txt")).unwrap();
+        std::fs::write(repo.join("README.md"), "# Modified\n").unwrap();
+        assert!(!same(|b| b.is_clean(repo)));
+        git(repo, &["checkout", "--quiet", "README.md"]);
+        assert!(same(|b| b.is_clean(repo)));
+
+        git(repo, &["checkout", "--quiet", "--detach"]);
+        assert_eq!(same(|b| b.current_branch(repo)), None);
+    }
+}
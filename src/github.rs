@@ -0,0 +1,377 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! GitHub REST API client.
+//!
+//! Used to execute [`crate::actions::GithubAction`]s unless the `gh` backend is
+//! selected. Exported shell scripts and workflows always use `gh`.
+
+use std::path::{Path, PathBuf};
+
+use octocrab::{
+    Octocrab,
+    params::{self, repos::Reference},
+};
+
+use crate::config::CONFIG;
+
+/// Backend selection for GitHub actions, see
+/// [`crate::Context::set_github_backend`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Call the GitHub REST API, authenticating with the token from
+    /// `GITHUB_TOKEN`, `GH_TOKEN` or else `gh auth token`.
+    #[default]
+    Api,
+    /// Run the `gh` binary.
+    Gh,
+}
+
+#[derive(Debug)]
+pub enum GithubError {
+    /// Neither `GITHUB_TOKEN` nor `GH_TOKEN` is set.
+    MissingToken,
+    /// None of the checkout's remotes points to a GitHub repository.
+    UnknownRepository(PathBuf),
+    /// GitHub rejected the request.
+    Api {
+        request: String,
+        status: u16,
+        message: String,
+    },
+    /// The request could not be performed.
+    Request { request: String, message: String },
+}
+
+impl std::fmt::Display for GithubError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingToken => write!(
+                fmt,
+                "No GitHub token found, set `GITHUB_TOKEN` or `GH_TOKEN`, log in with `gh auth \
+                 login`, or use `--github-backend gh`"
+            ),
+            Self::UnknownRepository(path) => write!(
+                fmt,
+                "Could not find a GitHub remote (`upstream` or `origin`) in `{}`",
+                path.display()
+            ),
+            Self::Api {
+                request,
+                status,
+                message,
+            } => write!(fmt, "{request} failed with HTTP status {status}: {message}"),
+            Self::Request { request, message } => write!(fmt, "{request} failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for GithubError {}
+
+impl GithubError {
+    fn from_octocrab(request: impl Into<String>, err: octocrab::Error) -> Self {
+        let request = request.into();
+        match err {
+            octocrab::Error::GitHub { source, .. } => Self::Api {
+                request,
+                status: source.status_code.as_u16(),
+                message: source.message,
+            },
+            // Other variants' `Display` includes a backtrace, only show the cause.
+            err => Self::Request {
+                request,
+                message: std::error::Error::source(&err)
+                    .map(ToString::to_string)
+                    .unwrap_or_else(|| err.to_string()),
+            },
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        matches!(self, Self::Api { status: 404, .. })
+    }
+}
+
+/// Owner and name of the GitHub repository at `url`, for both HTTPS and SSH
+/// remote URLs.
+pub fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let (_, path) = url
+        .split_once("github.com/")
+        .or_else(|| url.split_once("github.com:"))?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path.split_once('/')?;
+    if owner.is_empty() || name.is_empty() || name.contains('/') {
+        return None;
+    }
+    Some((owner.to_string(), name.to_string()))
+}
+
+/// Owner of the GitHub repository that remote `remote` of the local checkout
+/// `repository` refers to.
+pub fn remote_owner(
+    repository: &Path,
+    remote: &str,
+    context: &crate::Context,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let url = context
+        .git()
+        .remotes(repository)?
+        .into_iter()
+        .find(|r| r.name == remote)
+        .and_then(|r| r.url)
+        .ok_or_else(|| format!("Remote `{remote}` not found in `{}`", repository.display()))?;
+    parse_remote_url(&url)
+        .map(|(owner, _)| owner)
+        .ok_or_else(|| format!("Remote `{remote}` (`{url}`) is not a GitHub repository").into())
+}
+
+/// Token of the user logged in with `gh`, if it is installed.
+fn gh_auth_token(context: &crate::Context) -> Option<String> {
+    let output = std::process::Command::new(context.gh_bin())
+        .args(["auth", "token"])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let token = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if !output.status.success() || token.is_empty() {
+        return None;
+    }
+    log::debug!("Using GitHub token of `gh auth token`.");
+    Some(token)
+}
+
+/// Client for the GitHub repository a local checkout belongs to.
+#[derive(Debug, Clone)]
+pub struct Client {
+    crab: Octocrab,
+    /// Owner of the repository, from the `upstream` remote or `origin`.
+    pub owner: String,
+    pub name: String,
+    /// Owner of the `origin` remote, if it is a fork.
+    pub fork_owner: Option<String>,
+}
+
+impl Client {
+    pub fn new(
+        repository: &Path,
+        context: &crate::Context,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let token = match CONFIG.github_token() {
+            Some(token) => token.to_string(),
+            None => gh_auth_token(context).ok_or(GithubError::MissingToken)?,
+        };
+        let remotes = context
+            .git()
+            .remotes(repository)?
+            .into_iter()
+            .filter_map(|remote| Some((remote.name, parse_remote_url(remote.url.as_deref()?)?)))
+            .collect::<Vec<_>>();
+        let find = |name: &str| {
+            remotes
+                .iter()
+                .find(|(remote, _)| remote == name)
+                .map(|(_, repo)| repo.clone())
+        };
+        let origin = find("origin");
+        let (owner, name) = find("upstream")
+            .or_else(|| origin.clone())
+            .ok_or_else(|| GithubError::UnknownRepository(repository.to_path_buf()))?;
+        let fork_owner = origin
+            .map(|(origin_owner, _)| origin_owner)
+            .filter(|origin_owner| origin_owner != &owner);
+        let crab = Octocrab::builder()
+            .personal_token(token)
+            .build()
+            .map_err(|err| GithubError::from_octocrab("Creating GitHub client", err))?;
+        Ok(Self {
+            crab,
+            owner,
+            name,
+            fork_owner,
+        })
+    }
+
+    fn repos(&self) -> octocrab::repos::RepoHandler<'_> {
+        self.crab.repos(&self.owner, &self.name)
+    }
+
+    pub async fn default_branch(&self) -> Result<String, GithubError> {
+        let repo = self.repos().get().await.map_err(|err| {
+            GithubError::from_octocrab(format!("Fetching {}/{}", self.owner, self.name), err)
+        })?;
+        repo.default_branch.ok_or_else(|| GithubError::Request {
+            request: format!("Fetching {}/{}", self.owner, self.name),
+            message: "Repository has no default branch".to_string(),
+        })
+    }
+
+    /// Numbers of PRs in state `state` whose title contains `title`.
+    pub async fn find_prs(
+        &self,
+        title: &str,
+        state: params::State,
+    ) -> Result<Vec<u64>, GithubError> {
+        let request = "Listing pull requests";
+        let page = self
+            .crab
+            .pulls(&self.owner, &self.name)
+            .list()
+            .state(state)
+            .per_page(100)
+            .send()
+            .await
+            .map_err(|err| GithubError::from_octocrab(request, err))?;
+        let prs = self
+            .crab
+            .all_pages(page)
+            .await
+            .map_err(|err| GithubError::from_octocrab(request, err))?;
+        Ok(prs
+            .into_iter()
+            .filter(|pr| pr.title.as_deref().is_some_and(|t| t.contains(title)))
+            .map(|pr| pr.number)
+            .collect())
+    }
+
+    /// PR head of `branch`, in the fork if there is one.
+    pub fn head(&self, branch: &str) -> String {
+        match self.fork_owner {
+            Some(ref fork_owner) => format!("{fork_owner}:{branch}"),
+            None => branch.to_string(),
+        }
+    }
+
+    /// Open a PR from `head`, a branch optionally prefixed with the owner of
+    /// the fork it is in, and assign it to the authenticated user. Returns the
+    /// PR number.
+    pub async fn create_pr(&self, title: &str, head: &str, body: &str) -> Result<u64, GithubError> {
+        let base = self.default_branch().await?;
+        let pr = self
+            .crab
+            .pulls(&self.owner, &self.name)
+            .create(title, head, &base)
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| {
+                GithubError::from_octocrab(format!("Creating pull request from `{head}`"), err)
+            })?;
+        let login = self
+            .crab
+            .current()
+            .user()
+            .await
+            .map_err(|err| GithubError::from_octocrab("Fetching authenticated user", err))?
+            .login;
+        self.crab
+            .issues(&self.owner, &self.name)
+            .add_assignees(pr.number, &[&login])
+            .await
+            .map_err(|err| {
+                GithubError::from_octocrab(format!("Assigning pull request #{}", pr.number), err)
+            })?;
+        Ok(pr.number)
+    }
+
+    pub async fn close_pr(&self, number: u64) -> Result<(), GithubError> {
+        self.crab
+            .pulls(&self.owner, &self.name)
+            .update(number)
+            .state(params::pulls::State::Closed)
+            .send()
+            .await
+            .map_err(|err| {
+                GithubError::from_octocrab(format!("Closing pull request #{number}"), err)
+            })?;
+        Ok(())
+    }
+
+    pub async fn tag_exists(&self, tag: &str) -> Result<bool, GithubError> {
+        match self.repos().get_ref(&Reference::Tag(tag.to_string())).await {
+            Ok(_) => Ok(true),
+            Err(err) => {
+                let err = GithubError::from_octocrab(format!("Fetching tag `{tag}`"), err);
+                if err.is_not_found() {
+                    Ok(false)
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// Id of the release of `tag`, if there is one.
+    pub async fn release_id(&self, tag: &str) -> Result<Option<u64>, GithubError> {
+        match self.repos().releases().get_by_tag(tag).await {
+            Ok(release) => Ok(Some(release.id.into_inner())),
+            Err(err) => {
+                let err = GithubError::from_octocrab(format!("Fetching release `{tag}`"), err);
+                if err.is_not_found() {
+                    Ok(None)
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// Create release of existing `tag` and mark it as latest.
+    pub async fn create_release(
+        &self,
+        tag: &str,
+        title: &str,
+        notes: &str,
+    ) -> Result<(), GithubError> {
+        if !self.tag_exists(tag).await? {
+            return Err(GithubError::Request {
+                request: format!("Creating release `{tag}`"),
+                message: format!("Tag `{tag}` does not exist in {}/{}", self.owner, self.name),
+            });
+        }
+        self.repos()
+            .releases()
+            .create(tag)
+            .name(title)
+            .body(notes)
+            .make_latest(octocrab::repos::releases::MakeLatest::True)
+            .send()
+            .await
+            .map_err(|err| GithubError::from_octocrab(format!("Creating release `{tag}`"), err))?;
+        Ok(())
+    }
+
+    pub async fn delete_release(&self, tag: &str) -> Result<(), GithubError> {
+        let Some(id) = self.release_id(tag).await? else {
+            return Err(GithubError::Request {
+                request: format!("Deleting release `{tag}`"),
+                message: "Release does not exist".to_string(),
+            });
+        };
+        self.repos()
+            .releases()
+            .delete(id)
+            .await
+            .map_err(|err| GithubError::from_octocrab(format!("Deleting release `{tag}`"), err))
+    }
+}
@@ -20,11 +20,15 @@
 //
 // SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
 
+use std::sync::Arc;
+
 use async_trait::async_trait;
 
 use crate::{
     actions::{Action, CratesIOAction},
+    changelog::Changelog,
     crates_io::CratesIoAPIQuery,
+    workspace::{DependencyKind, Workspace},
 };
 
 #[derive(Debug)]
@@ -34,13 +38,124 @@ pub struct HealthCheckError {
 }
 
 #[async_trait]
-pub trait HealthCheck {
+pub trait HealthCheck: std::fmt::Debug {
     async fn exec(
         &self,
         context: &mut crate::Context,
     ) -> Result<Vec<HealthCheckError>, Box<dyn std::error::Error>>;
 }
 
+/// A health check that can be selected by its id, see [`REGISTRY`].
+#[derive(Debug)]
+pub struct RegisteredCheck {
+    pub id: &'static str,
+    pub description: &'static str,
+    /// Whether the check runs when no checks are selected explicitly.
+    pub enabled_by_default: bool,
+    /// Whether the check also applies to crates that are not published to
+    /// crates.io, see [`crate::workspace::Package::is_publishable`].
+    pub unpublishable: bool,
+    build: fn(String, std::path::PathBuf, Arc<Workspace>) -> Box<dyn HealthCheck>,
+}
+
+impl RegisteredCheck {
+    /// Instantiate the check for crate `crate_name` of `workspace`, in the
+    /// repository at `local_crate_path`.
+    pub fn build(
+        &self,
+        crate_name: String,
+        local_crate_path: std::path::PathBuf,
+        workspace: Arc<Workspace>,
+    ) -> Box<dyn HealthCheck> {
+        (self.build)(crate_name, local_crate_path, workspace)
+    }
+}
+
+/// All health checks, in the order they run.
+pub const REGISTRY: &[RegisteredCheck] = &[
+    RegisteredCheck {
+        id: "crate-owners",
+        description: "rust-vmm:gatekeepers and the CODEOWNERS are owners on crates.io",
+        enabled_by_default: true,
+        unpublishable: false,
+        build: |crate_name, local_crate_path, _workspace| {
+            Box::new(CheckCrateOwners {
+                crate_name,
+                local_crate_path,
+            })
+        },
+    },
+    RegisteredCheck {
+        id: "changelog",
+        description: "CHANGELOG.md parses and its latest release matches the crate version",
+        enabled_by_default: true,
+        unpublishable: false,
+        build: |crate_name, local_crate_path, workspace| {
+            Box::new(CheckChangelog {
+                crate_name,
+                local_crate_path,
+                workspace,
+            })
+        },
+    },
+    RegisteredCheck {
+        id: "path-dependencies",
+        description: "Path dependencies have versions matching the crates they point to",
+        enabled_by_default: true,
+        unpublishable: false,
+        build: |crate_name, local_crate_path, workspace| {
+            Box::new(CheckPathDependencies {
+                crate_name,
+                local_crate_path,
+                workspace,
+            })
+        },
+    },
+    RegisteredCheck {
+        id: "publish",
+        description: "The `publish` setting agrees with the crate's presence on crates.io",
+        enabled_by_default: true,
+        unpublishable: true,
+        build: |crate_name, local_crate_path, workspace| {
+            Box::new(CheckPublish {
+                crate_name,
+                local_crate_path,
+                workspace,
+            })
+        },
+    },
+];
+
+/// Checks to run: the ones in `check` if any, otherwise the ones enabled by
+/// default, without the ones in `skip`. Fails on unknown ids.
+pub fn select(
+    check: &[String],
+    skip: &[String],
+) -> Result<Vec<&'static RegisteredCheck>, Box<dyn std::error::Error>> {
+    if let Some(unknown) = check
+        .iter()
+        .chain(skip)
+        .find(|id| !REGISTRY.iter().any(|c| c.id == id.as_str()))
+    {
+        return Err(format!(
+            "Unknown health check `{unknown}`, expected one of {:?}",
+            REGISTRY.iter().map(|c| c.id).collect::<Vec<_>>()
+        )
+        .into());
+    }
+    Ok(REGISTRY
+        .iter()
+        .filter(|c| {
+            if check.is_empty() {
+                c.enabled_by_default
+            } else {
+                check.iter().any(|id| id == c.id)
+            }
+        })
+        .filter(|c| !skip.iter().any(|id| id == c.id))
+        .collect())
+}
+
 #[derive(Debug)]
 pub struct CheckCrateOwners {
     pub crate_name: String,
@@ -135,3 +250,221 @@ impl HealthCheck for CheckCrateOwners {
         Ok(results)
     }
 }
+
+/// Check that the crate's `CHANGELOG.md` parses, that its latest release is
+/// the version in `Cargo.toml`, and that every release is dated and has no
+/// empty sections.
+#[derive(Debug)]
+pub struct CheckChangelog {
+    pub crate_name: String,
+    pub local_crate_path: std::path::PathBuf,
+    pub workspace: Arc<Workspace>,
+}
+
+#[async_trait]
+impl HealthCheck for CheckChangelog {
+    async fn exec(
+        &self,
+        context: &mut crate::Context,
+    ) -> Result<Vec<HealthCheckError>, Box<dyn std::error::Error>> {
+        log::info!("Running {:?}", self);
+        let c = crate::utilities::repository::from_workspace(
+            &self.crate_name,
+            &self.workspace,
+            context,
+        )?;
+        let crate_dir = c.package.dir();
+        let mut results = vec![];
+        let mut error = |description: String| {
+            log::error!("{description}");
+            results.push(HealthCheckError {
+                description,
+                fix_action: None,
+            });
+        };
+        let Some(path) = crate::changelog::find(crate_dir, &self.local_crate_path) else {
+            error(format!("{:?} has no CHANGELOG.md.", self.crate_name));
+            return Ok(results);
+        };
+        let changelog = match Changelog::parse(&std::fs::read_to_string(&path)?) {
+            Ok(changelog) => changelog,
+            Err(err) => {
+                error(format!("Could not parse `{}`: {err}", path.display()));
+                return Ok(results);
+            }
+        };
+        let version = &c.package.version;
+        match changelog.latest().and_then(|r| r.version.as_ref()) {
+            Some(latest) if latest == version => {
+                log::info!("OK: latest release in changelog is v{version}.");
+            }
+            latest => error(format!(
+                "Latest release in `{}` is {}, but {:?} is at version {version}.",
+                path.display(),
+                latest.map_or_else(|| "missing".to_string(), |v| format!("v{v}")),
+                self.crate_name
+            )),
+        }
+        for release in &changelog.releases {
+            let Some(ref release_version) = release.version else {
+                continue;
+            };
+            match release.date {
+                None => error(format!(
+                    "Release v{release_version} in `{}` has no date.",
+                    path.display()
+                )),
+                Some(ref date) if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() => {
+                    error(format!(
+                        "Release v{release_version} in `{}` has date `{date}`, expected \
+                         YYYY-MM-DD.",
+                        path.display()
+                    ))
+                }
+                Some(_) => {}
+            }
+            if release.is_empty() {
+                error(format!(
+                    "Release v{release_version} in `{}` has no changes.",
+                    path.display()
+                ));
+            }
+            for section in release.sections.iter().filter(|s| s.body.is_empty()) {
+                error(format!(
+                    "Release v{release_version} in `{}` has an empty `{}` section.",
+                    path.display(),
+                    section.kind
+                ));
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Check that the crate's path dependencies require the version they point
+/// to, since published crates depend on that version on crates.io instead.
+#[derive(Debug)]
+pub struct CheckPathDependencies {
+    pub crate_name: String,
+    pub local_crate_path: std::path::PathBuf,
+    pub workspace: Arc<Workspace>,
+}
+
+#[async_trait]
+impl HealthCheck for CheckPathDependencies {
+    async fn exec(
+        &self,
+        context: &mut crate::Context,
+    ) -> Result<Vec<HealthCheckError>, Box<dyn std::error::Error>> {
+        log::info!("Running {:?}", self);
+        let c = crate::utilities::repository::from_workspace(
+            &self.crate_name,
+            &self.workspace,
+            context,
+        )?;
+        let contents = std::fs::read_to_string(&c.manifest_path)?;
+        let mut results = vec![];
+        for (dependency, version) in self
+            .workspace
+            .path_dependency_versions(&c.package, context)?
+        {
+            let name = dependency.key();
+            let mut location = format!("`[{}]`", dependency.table());
+            if crate::manifest::inherits_dependency(&contents, name)? {
+                location.push_str(" (inherited from `[workspace.dependencies]`)");
+            }
+            let description = match dependency.version_req() {
+                None if dependency.kind == DependencyKind::Development => continue,
+                None => format!(
+                    "Path dependency `{name}` of {:?} in {location} has no version, expected \
+                     \"{version}\".",
+                    self.crate_name
+                ),
+                Some(requirement) => {
+                    let matches = semver::VersionReq::parse(requirement)
+                        .ok()
+                        .zip(semver::Version::parse(&version).ok())
+                        .is_some_and(|(requirement, version)| requirement.matches(&version));
+                    if matches {
+                        log::info!(
+                            "OK: `{name}` requirement \"{requirement}\" matches v{version}."
+                        );
+                        continue;
+                    }
+                    format!(
+                        "Path dependency `{name}` of {:?} in {location} requires \
+                         \"{requirement}\", which does not match its version {version}.",
+                        self.crate_name
+                    )
+                }
+            };
+            log::error!("{description}");
+            results.push(HealthCheckError {
+                description,
+                fix_action: None,
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// Check that the crate's `publish` setting agrees with crates.io: crates
+/// that may be published to crates.io should be there and crates that may
+/// not shouldn't.
+#[derive(Debug)]
+pub struct CheckPublish {
+    pub crate_name: String,
+    pub local_crate_path: std::path::PathBuf,
+    pub workspace: Arc<Workspace>,
+}
+
+#[async_trait]
+impl HealthCheck for CheckPublish {
+    async fn exec(
+        &self,
+        context: &mut crate::Context,
+    ) -> Result<Vec<HealthCheckError>, Box<dyn std::error::Error>> {
+        log::info!("Running {:?}", self);
+        let c = crate::utilities::repository::from_workspace(
+            &self.crate_name,
+            &self.workspace,
+            context,
+        )?;
+        let reply = crate::crates_io::get_crate::Query {
+            crate_name: &self.crate_name,
+        }
+        .get(context)
+        .await?;
+        let published = match reply {
+            Ok(_) => true,
+            Err(err) if err.errors.len() == 1 && err.errors[0].detail == "Not Found" => false,
+            Err(other_err) => {
+                return Err(format!("crates.io error: {other_err:?}").into());
+            }
+        };
+        let description = match (c.package.is_publishable(), published) {
+            (true, false) => format!(
+                "{:?} is publishable to crates.io but has not been published; set `publish = \
+                 false` if it is internal.",
+                self.crate_name
+            ),
+            (false, true) => format!(
+                "{:?} is on crates.io but its manifest has `publish = {}`.",
+                self.crate_name,
+                c.package.publish_setting()
+            ),
+            _ => {
+                log::info!(
+                    "OK: {:?} publish setting matches crates.io.",
+                    self.crate_name
+                );
+                return Ok(vec![]);
+            }
+        };
+        log::error!("{description}");
+        Ok(vec![HealthCheckError {
+            description,
+            fix_action: None,
+        }])
+    }
+}
@@ -20,20 +20,42 @@
 //
 // SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
 
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub mod actions;
+pub mod changelog;
 pub mod cli;
 pub mod config;
 pub mod crates_io;
+pub mod git;
+pub mod github;
 pub mod health_checks;
+pub mod logging;
+pub mod manifest;
+pub mod plan;
+pub mod steps;
 pub mod utilities;
+pub mod workflow;
+pub mod workspace;
 
-#[derive(Debug)]
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod test_utils;
+
+/// Settings and shared state for executing actions.
+///
+/// Clones share the crates.io rate limiter, so they can be handed to
+/// concurrently executing plan items.
+#[derive(Debug, Clone)]
 pub struct Context {
     cargo_bin: Option<std::path::PathBuf>,
     gh_bin: Option<std::path::PathBuf>,
-    last_crates_io_call: Instant,
+    git: Arc<dyn git::GitBackend>,
+    github_backend: github::Backend,
+    last_crates_io_call: Arc<tokio::sync::Mutex<Instant>>,
 }
 
 impl Default for Context {
@@ -48,7 +70,9 @@ impl Context {
         Self {
             cargo_bin: None,
             gh_bin: None,
-            last_crates_io_call,
+            git: git::Backend::default().build().into(),
+            github_backend: github::Backend::default(),
+            last_crates_io_call: Arc::new(tokio::sync::Mutex::new(last_crates_io_call)),
         }
     }
 
@@ -59,6 +83,10 @@ impl Context {
             .unwrap_or_else(|| std::ffi::OsStr::new("cargo"))
     }
 
+    pub fn set_cargo_bin(&mut self, cargo_bin: Option<std::path::PathBuf>) {
+        self.cargo_bin = cargo_bin;
+    }
+
     pub fn gh_bin(&self) -> impl AsRef<std::ffi::os_str::OsStr> {
         self.gh_bin
             .as_deref()
@@ -66,14 +94,38 @@ impl Context {
             .unwrap_or_else(|| std::ffi::OsStr::new("gh"))
     }
 
-    pub async fn crates_io_call(&mut self) {
+    pub fn set_gh_bin(&mut self, gh_bin: Option<std::path::PathBuf>) {
+        self.gh_bin = gh_bin;
+    }
+
+    /// Backend for querying local git repositories.
+    pub fn git(&self) -> &dyn git::GitBackend {
+        self.git.as_ref()
+    }
+
+    pub fn set_git_backend(&mut self, backend: git::Backend) {
+        self.git = backend.build().into();
+    }
+
+    pub fn github_backend(&self) -> github::Backend {
+        self.github_backend
+    }
+
+    pub fn set_github_backend(&mut self, backend: github::Backend) {
+        self.github_backend = backend;
+    }
+
+    /// Wait until crates.io may be called again without exceeding its rate
+    /// limit. Other calls, including those of clones of this context, wait
+    /// until the returned guard is dropped.
+    pub async fn crates_io_call(&self) -> tokio::sync::OwnedMutexGuard<Instant> {
         const ONE_SECOND: Duration = Duration::from_secs(1);
 
-        if self.last_crates_io_call.elapsed() >= ONE_SECOND {
-            self.last_crates_io_call = Instant::now();
-        } else {
-            tokio::time::sleep(Duration::from_secs(1)).await;
-            self.last_crates_io_call = Instant::now();
+        let mut last_crates_io_call = self.last_crates_io_call.clone().lock_owned().await;
+        if last_crates_io_call.elapsed() < ONE_SECOND {
+            tokio::time::sleep(ONE_SECOND - last_crates_io_call.elapsed()).await;
         }
+        *last_crates_io_call = Instant::now();
+        last_crates_io_call
     }
 }
@@ -0,0 +1,104 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Grouping of log output of concurrently running tasks.
+//!
+//! Records logged inside [`grouped`] are held back and emitted together by
+//! [`flush`], so that the output of concurrently executed plan items does not
+//! interleave.
+
+use std::cell::RefCell;
+
+tokio::task_local! {
+    static BUFFER: RefCell<Vec<Line>>;
+}
+
+/// A held back log record.
+#[derive(Debug, Clone)]
+pub struct Line {
+    level: log::Level,
+    target: String,
+    message: String,
+}
+
+/// Logger that buffers records of [`grouped`] tasks and passes everything else
+/// on to `inner`.
+pub struct GroupingLogger<L> {
+    inner: L,
+}
+
+impl<L: log::Log> log::Log for GroupingLogger<L> {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        let buffered = BUFFER.try_with(|buffer| {
+            buffer.borrow_mut().push(Line {
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            })
+        });
+        if buffered.is_err() {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install `logger` wrapped in a [`GroupingLogger`] as the global logger.
+pub fn init(logger: env_logger::Logger) {
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(GroupingLogger { inner: logger }))
+        .expect("logger is only initialized once");
+}
+
+/// Run `future`, holding back its log records and returning them along with
+/// its output.
+pub async fn grouped<F: Future>(future: F) -> (F::Output, Vec<Line>) {
+    BUFFER
+        .scope(RefCell::new(vec![]), async {
+            let output = future.await;
+            (output, BUFFER.with(|buffer| buffer.take()))
+        })
+        .await
+}
+
+/// Emit log records held back by [`grouped`].
+pub fn flush(lines: Vec<Line>) {
+    for line in lines {
+        log::logger().log(
+            &log::Record::builder()
+                .level(line.level)
+                .target(&line.target)
+                .args(format_args!("{}", line.message))
+                .build(),
+        );
+    }
+}
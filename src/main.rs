@@ -20,28 +20,41 @@
 //
 // SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
 
+use std::sync::Arc;
+
 use rust_vmm_helper_cli::{
     Context,
-    actions::{Action, CratesIOAction, GithubAction, LocalAction, Plan, PlanItem},
-    cli::{ActionCommand, Cli, Command},
+    actions::{Action, Condition, CratesIOAction, GithubAction, LocalAction, Plan, PlanItem},
+    changelog,
+    cli::{ActionCommand, Cli, Command, PlanVariables},
     config::CONFIG,
     crates_io::CratesIoAPIQuery,
+    github,
     health_checks::{self, HealthCheck, HealthCheckError},
-    utilities,
+    logging, manifest,
+    plan::{self, ConfirmFn, Confirmation, ItemOutcome, Journal, PlanGraph},
+    steps::EditedFiles,
+    utilities, workflow,
+    workspace::{DependencyKind, Workspace},
 };
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::new();
 
-    env_logger::Builder::new()
-        .parse_filters(&std::env::var("RUST_LOG").unwrap_or_else(|_| "debug".to_string()))
-        .init();
+    logging::init(
+        env_logger::Builder::new()
+            .parse_filters(&std::env::var("RUST_LOG").unwrap_or_else(|_| "debug".to_string()))
+            .build(),
+    );
 
     log::debug!("Logging initialized.");
     _ = CONFIG.check();
 
     let mut context = Context::new();
+    context.set_cargo_bin(cli.cargo_bin.clone());
+    context.set_git_backend(cli.git_backend);
+    context.set_github_backend(cli.github_backend);
 
     //let reply = rust_vmm_helper_cli::crates_io::reverse_dependencies::Query {
     //    crate_name: "vm-memory",
@@ -61,19 +74,69 @@ async fn main() {
         Command::HealthCheck {
             repository,
             package,
+            check,
+            skip,
             fix,
             json_plan_output,
         } => {
-            let crate_name = package.unwrap_or_else(|| todo!());
+            let selected = match health_checks::select(&check, &skip) {
+                Ok(selected) => selected,
+                Err(err) => {
+                    log::error!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            let workspace = match Workspace::load(&repository, &context) {
+                Ok(workspace) => Arc::new(workspace),
+                Err(err) => {
+                    log::error!(
+                        "Could not read workspace at {}: {err}",
+                        repository.display()
+                    );
+                    std::process::exit(1);
+                }
+            };
+            let crate_names = match package {
+                Some(crate_name) => vec![crate_name],
+                None => workspace.members.iter().map(|p| p.name.clone()).collect(),
+            };
             let json_plan_output = json_plan_output
                 .as_ref()
                 .map(|p| (std::fs::File::create(p).unwrap(), p));
-            let check_owners = health_checks::CheckCrateOwners {
-                crate_name,
-                local_crate_path: repository,
-            };
+            let checks: Vec<Box<dyn HealthCheck>> = crate_names
+                .iter()
+                .flat_map(|crate_name| {
+                    let publishable = workspace
+                        .package(crate_name)
+                        .is_none_or(|p| p.is_publishable());
+                    selected
+                        .iter()
+                        .filter(move |c| {
+                            if !publishable && !c.unpublishable {
+                                log::info!(
+                                    "Skipping check `{}` for {crate_name:?}, it is not published.",
+                                    c.id
+                                );
+                                return false;
+                            }
+                            true
+                        })
+                        .map(|c| c.build(crate_name.clone(), repository.clone(), workspace.clone()))
+                })
+                .collect();
+            let mut errors = vec![];
+            let mut failed = false;
+            for check in &checks {
+                match check.exec(&mut context).await {
+                    Ok(check_errors) => errors.extend(check_errors),
+                    Err(err) => {
+                        log::error!("Could not run health check {check:?}: {err}");
+                        failed = true;
+                    }
+                }
+            }
             let mut plan_actions = vec![];
-            for error in check_owners.exec(&mut context).await.unwrap() {
+            for error in errors {
                 let HealthCheckError {
                     description,
                     fix_action,
@@ -82,8 +145,12 @@ async fn main() {
                     continue;
                 };
                 plan_actions.push(PlanItem {
+                    id: None,
                     description: Some(description.clone()),
+                    depends_on: Some(vec![]),
+                    preconditions: vec![],
                     action: fix_action.clone(),
+                    postconditions: vec![],
                     continue_on_error: true,
                 });
                 if !fix {
@@ -95,12 +162,27 @@ async fn main() {
             }
             if let Some((mut writer, path)) = json_plan_output {
                 log::info!("Serializing plan to `{}`...", path.display());
-                serde_json::to_writer_pretty(&mut writer, &Plan {
-                    items: plan_actions,
-                })
-                .expect("Could not serialize plan to file");
+                serde_json::to_writer_pretty(&mut writer, &Plan::new(plan_actions))
+                    .expect("Could not serialize plan to file");
                 log::info!("Wrote plan to `{}`.", path.display());
             }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Command::ListChecks => {
+            for check in health_checks::REGISTRY {
+                println!(
+                    "{:<20} {:<9} {}",
+                    check.id,
+                    if check.enabled_by_default {
+                        "default"
+                    } else {
+                        "opt-in"
+                    },
+                    check.description
+                );
+            }
         }
         Command::Action {
             action,
@@ -117,24 +199,46 @@ async fn main() {
                     // https://github.com/rust-vmm/community/blob/main/docs/crate_release.md
                     // 1. "Prepare any last-minute changes in a pull request, if necessary." Assume
                     //    this is done already.
-                    // 2. "Update the CHANGELOG.md file in the root of the crate's folder. The
-                    //    first paragraph should be titled with the version of the new release[..]"
-                    //    TODO: use sth like <https://docs.rs/parse-changelog/latest/parse_changelog/>?
+                    // 2. "Update the CHANGELOG.md file in the root of the crate's folder. The first
+                    //    paragraph should be titled with the version of the new release[..]" Done
+                    //    by `prepare-release`, the version's section is used as release notes and
+                    //    tag message.
                     // 3. "Update the version field in the Cargo.toml file from the crate's root
                     //    folder." TODO: check that it's done already.
                     // 4. "If the crate is part of a workspace and has a path dependency, update
                     //    that dependency in Cargo.toml with a version that is published on
                     //    crates.io as explained in the introduction. This version should be the
-                    //    latest one released." TODO
+                    //    latest one released." Done by `prepare-release`.
+                    let workspace = Workspace::load(&repository, &context)
+                        .expect("Could not read repository path");
                     let crates_to_publish: Vec<utilities::repository::Crate> = package
                         .iter()
-                        .map(|p| utilities::repository::from_dir(p, &repository))
+                        .map(|p| utilities::repository::from_workspace(p, &workspace, &context))
                         .collect::<Result<Vec<_>, _>>()
                         .expect("Could not read repository path");
+                    let unpublishable = crates_to_publish
+                        .iter()
+                        .filter(|c| !c.package.is_publishable())
+                        .map(|c| {
+                            format!(
+                                "{:?} (publish = {})",
+                                c.package.name,
+                                c.package.publish_setting()
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    if !unpublishable.is_empty() {
+                        log::error!(
+                            "Packages {} are not publishable to crates.io according to their \
+                             manifests, aborting.",
+                            unpublishable.join(", ")
+                        );
+                        std::process::exit(1);
+                    }
                     let mut crates: Vec<utilities::repository::Crate> = vec![];
                     for c in crates_to_publish {
                         let reply = rust_vmm_helper_cli::crates_io::get_crate::Query {
-                            crate_name: &c.manifest.package.name,
+                            crate_name: &c.package.name,
                         }
                         .get(&mut context)
                         .await
@@ -143,16 +247,16 @@ async fn main() {
 
                         log::debug!(
                             "get_crate for {:?} API reply was: {:?}",
-                            c.manifest.package.name,
+                            c.package.name,
                             reply
                         );
-                        if c.manifest.package.version != reply.crate_field.default_version {
+                        if c.package.version != reply.crate_field.default_version {
                             crates.push(c);
                         } else {
                             log::info!(
                                 "Skipping package {:?} because local checked out version matches \
                                  published version on crates.io (={:?})",
-                                c.manifest.package.name,
+                                c.package.name,
                                 reply.crate_field.default_version
                             );
                         }
@@ -161,195 +265,761 @@ async fn main() {
                         log::error!("Nothing to do, aborting.");
                         return;
                     }
+                    // Crates must be published after their dependencies, see
+                    // `wait-*` items below.
+                    let crates = match utilities::repository::sort_by_dependencies(crates) {
+                        Ok(crates) => crates,
+                        Err(err) => {
+                            log::error!("{err}, aborting.");
+                            return;
+                        }
+                    };
+                    let dependencies = crates
+                        .iter()
+                        .map(|c| c.dependencies_among(&crates))
+                        .collect::<Vec<_>>();
+                    let release_notes = crates
+                        .iter()
+                        .map(|c| release_notes(c, &repository))
+                        .collect::<Vec<_>>();
                     plan_actions.push(PlanItem {
+                        id: Some("assert-default-branch".to_string()),
                         description: Some("Ensure we are in default branch".to_string()),
+                        depends_on: Some(vec![]),
+                        preconditions: vec![],
                         action: Action::Local {
                             repository: repository.clone(),
                             inner: LocalAction::AssertDefaultBranch,
                         },
+                        postconditions: vec![],
                         continue_on_error: false,
                     });
-                    for c in &crates {
+                    for (c, dependencies) in crates.iter().zip(&dependencies) {
+                        // Packaging resolves dependencies from crates.io, so even the dry run
+                        // needs their new versions to be published.
+                        let mut depends_on = vec!["assert-default-branch".to_string()];
+                        depends_on.extend(dependencies.iter().map(|dep| format!("wait-{dep}")));
                         plan_actions.push(PlanItem {
+                            id: Some(format!("dry-run-{}", c.package.name)),
                             description: Some(format!(
                                 "Publish package `{}` to crates.io (dry run)",
-                                c.manifest.package.name.clone()
+                                c.package.name.clone()
                             )),
+                            depends_on: Some(depends_on),
+                            preconditions: vec![Condition::WorkingTreeClean],
                             action: Action::CratesIO {
                                 repository: repository.clone(),
                                 inner: CratesIOAction::Publish {
-                                    crate_name: c.manifest.package.name.clone(),
+                                    crate_name: c.package.name.clone(),
                                     dry_run: true,
                                 },
                             },
+                            postconditions: vec![],
                             continue_on_error: false,
                         });
                     }
 
                     let new_tags: Vec<String> = crates
                         .iter()
-                        .map(|c| {
-                            format!(
-                                "{}-v{}",
-                                c.manifest.package.name, c.manifest.package.version
-                            )
-                        })
+                        .map(|c| format!("{}-v{}", c.package.name, c.package.version))
                         .collect();
                     assert!(!new_tags.is_empty());
-                    plan_actions.push(PlanItem {
-                        description: Some(format!("Create tags `{}`", new_tags.join(","))),
-                        action: Action::Local {
-                            repository: repository.clone(),
-                            inner: LocalAction::CreateTags {
-                                new_tags: new_tags.clone(),
+                    // Tags are created and pushed per crate, so that a failure for one crate
+                    // does not hold back the release of the others.
+                    for ((c, tag), notes) in crates.iter().zip(new_tags.iter()).zip(&release_notes)
+                    {
+                        plan_actions.push(PlanItem {
+                            id: Some(format!("create-tag-{}", c.package.name)),
+                            description: Some(format!("Create tag `{tag}`")),
+                            depends_on: Some(vec![format!("dry-run-{}", c.package.name)]),
+                            preconditions: vec![Condition::Not(Box::new(Condition::TagExists {
+                                tag: tag.clone(),
+                                remote: None,
+                            }))],
+                            action: Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::CreateTags {
+                                    new_tags: vec![tag.clone()],
+                                    message: notes
+                                        .as_ref()
+                                        .map(|notes| format!("{tag}\n\n{notes}")),
+                                },
                             },
-                        },
-                        continue_on_error: false,
-                    });
+                            postconditions: vec![Condition::TagExists {
+                                tag: tag.clone(),
+                                remote: None,
+                            }],
+                            continue_on_error: false,
+                        });
+                    }
                     // 7. "Push the tag to the upstream repository: git push upstream
                     //    vm-awesome-v1.2.0. In this example, the upstream remote points to the
                     //    original repository (not your fork)."
-                    plan_actions.push(PlanItem {
-                        description: Some(format!("Push tags `{}` to remote", new_tags.join(","))),
-                        action: Action::Local {
-                            repository: repository.clone(),
-                            inner: LocalAction::PublishTags {
-                                tags: new_tags.clone(),
-                                remote: None,
+                    for (c, tag) in crates.iter().zip(new_tags.iter()) {
+                        plan_actions.push(PlanItem {
+                            id: Some(format!("push-tag-{}", c.package.name)),
+                            description: Some(format!("Push tag `{tag}` to remote")),
+                            depends_on: Some(vec![format!("create-tag-{}", c.package.name)]),
+                            preconditions: vec![],
+                            action: Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::PublishTags {
+                                    tags: vec![tag.clone()],
+                                    remote: None,
+                                },
                             },
-                        },
-                        continue_on_error: false,
-                    });
+                            postconditions: vec![Condition::TagExists {
+                                tag: tag.clone(),
+                                remote: Some("upstream".to_string()),
+                            }],
+                            continue_on_error: false,
+                        });
+                    }
                     // 8. "Create a GitHub release. Go to the Releases page in the crate's
                     //    repository and click Draft a new release (button on the right). In Tag
                     //    version, pick the newly pushed tag. In Release title, write the tag name
                     //    including v (example: vm-awesome-v1.2.3). The description should be the
                     //    new version's changelog section. Click Publish release."
-                    for (c, tag) in crates.iter().zip(new_tags.iter()) {
+                    for ((c, tag), notes) in crates.iter().zip(new_tags.iter()).zip(release_notes) {
                         plan_actions.push(PlanItem {
+                            id: Some(format!("release-{}", c.package.name)),
                             description: Some(format!(
                                 "Create a GitHub release for crate `{}` v{}",
-                                c.manifest.package.name.clone(),
-                                c.manifest.package.version.clone(),
+                                c.package.name.clone(),
+                                c.package.version.clone(),
                             )),
+                            depends_on: Some(vec![format!("push-tag-{}", c.package.name)]),
+                            preconditions: vec![],
                             action: Action::Github {
                                 repository: repository.clone(),
                                 inner: GithubAction::CreateRelease {
-                                    crate_name: c.manifest.package.name.clone(),
+                                    crate_name: c.package.name.clone(),
                                     tag: tag.clone(),
-                                    version: c.manifest.package.version.clone(),
+                                    version: c.package.version.clone(),
+                                    notes,
                                 },
                             },
+                            postconditions: vec![],
                             continue_on_error: false,
                         });
                     }
                     // 9. "Publish the new version to crates.io. To double-check what's being
                     //    published, do a dry run first. Make sure your HEAD is on the release tag."
                     for c in &crates {
+                        let published = Condition::CrateVersionPublished {
+                            crate_name: c.package.name.clone(),
+                            version: c.package.version.clone(),
+                        };
                         plan_actions.push(PlanItem {
+                            id: Some(format!("publish-{}", c.package.name)),
                             description: Some(format!(
                                 "Publish package `{}` to crates.io",
-                                c.manifest.package.name.clone()
+                                c.package.name.clone()
                             )),
+                            depends_on: Some(vec![format!("release-{}", c.package.name)]),
+                            preconditions: vec![Condition::Not(Box::new(published.clone()))],
                             action: Action::CratesIO {
                                 repository: repository.clone(),
                                 inner: CratesIOAction::Publish {
-                                    crate_name: c.manifest.package.name.clone(),
+                                    crate_name: c.package.name.clone(),
                                     dry_run: false,
                                 },
                             },
+                            postconditions: vec![published],
+                            continue_on_error: false,
+                        });
+                    }
+                    for c in &crates {
+                        let name = &c.package.name;
+                        if !dependencies.iter().flatten().any(|dep| dep == name) {
+                            continue;
+                        }
+                        plan_actions.push(PlanItem {
+                            id: Some(format!("wait-{name}")),
+                            description: Some(format!(
+                                "Wait for package `{name}` v{} to be available on crates.io",
+                                c.package.version
+                            )),
+                            depends_on: Some(vec![format!("publish-{name}")]),
+                            preconditions: vec![],
+                            action: Action::CratesIO {
+                                repository: repository.clone(),
+                                inner: CratesIOAction::WaitForVersion {
+                                    crate_name: name.clone(),
+                                    version: c.package.version.clone(),
+                                    timeout: CratesIOAction::WAIT_TIMEOUT,
+                                },
+                            },
+                            postconditions: vec![],
+                            continue_on_error: false,
+                        });
+                    }
+                }
+                ActionCommand::PrepareRelease {
+                    package,
+                    version,
+                    fork,
+                    branch,
+                } => {
+                    // Steps 2. to 4. of
+                    // https://github.com/rust-vmm/community/blob/main/docs/crate_release.md,
+                    // submitted as a pull request.
+                    let workspace = Workspace::load(&repository, &context)
+                        .expect("Could not read repository path");
+                    let c = utilities::repository::from_workspace(&package, &workspace, &context)
+                        .expect("Could not read repository path");
+                    if c.package.version == version {
+                        log::error!(
+                            "Package {package:?} is already at version {version:?}, aborting."
+                        );
+                        return;
+                    }
+                    let crate_dir = c.package.dir();
+                    let manifest = c
+                        .manifest_path
+                        .strip_prefix(&repository)
+                        .unwrap()
+                        .to_path_buf();
+                    let contents = std::fs::read_to_string(&c.manifest_path).unwrap();
+                    let root_manifest = workspace
+                        .root
+                        .join("Cargo.toml")
+                        .strip_prefix(&repository)
+                        .unwrap()
+                        .to_path_buf();
+                    // An inherited version can only be bumped for the whole workspace.
+                    let workspace_version = manifest::inherits_package_version(&contents).unwrap();
+                    if workspace_version {
+                        let others = workspace
+                            .members
+                            .iter()
+                            .filter(|member| member.name != package)
+                            .filter(|member| {
+                                std::fs::read_to_string(&member.manifest_path).is_ok_and(
+                                    |contents| {
+                                        manifest::inherits_package_version(&contents)
+                                            .unwrap_or(false)
+                                    },
+                                )
+                            })
+                            .map(|member| format!("`{}`", member.name))
+                            .collect::<Vec<_>>();
+                        if !others.is_empty() {
+                            log::warn!(
+                                "Package {package:?} inherits its version from the workspace, {} \
+                                 will be bumped to {version} as well.",
+                                others.join(", ")
+                            );
+                        }
+                    }
+                    let changelog = changelog::find(crate_dir, &repository);
+                    let body = match changelog {
+                        Some(ref path) => {
+                            let contents = std::fs::read_to_string(path).unwrap();
+                            let parsed = match changelog::Changelog::parse(&contents) {
+                                Ok(parsed) => parsed,
+                                Err(err) => {
+                                    log::error!("Could not parse `{}`: {err}", path.display());
+                                    return;
+                                }
+                            };
+                            let Some(unreleased) = parsed.unreleased().filter(|r| !r.is_empty())
+                            else {
+                                log::error!(
+                                    "`{}` has no unreleased changes to release, aborting.",
+                                    path.display()
+                                );
+                                return;
+                            };
+                            Some(unreleased.notes())
+                        }
+                        None => {
+                            log::warn!("No CHANGELOG.md found for package {package:?}.");
+                            None
+                        }
+                    };
+                    let changelog =
+                        changelog.map(|path| path.strip_prefix(&repository).unwrap().to_path_buf());
+                    // Path dependencies must require the version that is (about to be)
+                    // published.
+                    let mut dependency_updates: Vec<(String, String, std::path::PathBuf, bool)> =
+                        vec![];
+                    let path_dependencies =
+                        match workspace.path_dependency_versions(&c.package, &context) {
+                            Ok(path_dependencies) => path_dependencies,
+                            Err(err) => {
+                                log::error!("Could not read path dependencies: {err}, aborting.");
+                                return;
+                            }
+                        };
+                    for (dependency, dependency_version) in path_dependencies {
+                        let up_to_date = match dependency.version_req() {
+                            None => dependency.kind == DependencyKind::Development,
+                            Some(req) => {
+                                semver::VersionReq::parse(req).ok()
+                                    == semver::VersionReq::parse(&dependency_version).ok()
+                            }
+                        };
+                        let name = dependency.key();
+                        if up_to_date || dependency_updates.iter().any(|(n, ..)| n == name) {
+                            continue;
+                        }
+                        // Inherited dependencies are updated for the whole workspace.
+                        let inherited = manifest::inherits_dependency(&contents, name).unwrap();
+                        let dependency_manifest = if inherited {
+                            root_manifest.clone()
+                        } else {
+                            manifest.clone()
+                        };
+                        dependency_updates.push((
+                            name.to_string(),
+                            dependency_version,
+                            dependency_manifest,
+                            inherited,
+                        ));
+                    }
+                    let branch = branch.unwrap_or_else(|| format!("release-{package}-v{version}"));
+                    // `assert-default-branch` checks that this is the default branch before the
+                    // release branch is created from it, and rolling back returns to it.
+                    let base = match context.git().current_branch(&repository) {
+                        Ok(Some(base)) => base,
+                        Ok(None) => {
+                            log::error!(
+                                "HEAD is detached, check out the default branch, aborting."
+                            );
+                            return;
+                        }
+                        Err(err) => {
+                            log::error!("Could not read current branch: {err}, aborting.");
+                            return;
+                        }
+                    };
+                    // The PR is opened from the release branch in the fork it is pushed to.
+                    let fork_owner = match github::remote_owner(&repository, &fork, &context) {
+                        Ok(fork_owner) => fork_owner,
+                        Err(err) => {
+                            log::error!("Could not find owner of fork: {err}, aborting.");
+                            return;
+                        }
+                    };
+                    let mut items = vec![
+                        (
+                            "assert-default-branch".to_string(),
+                            "Ensure we are in default branch".to_string(),
+                            vec![Condition::WorkingTreeClean],
+                            Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::AssertDefaultBranch,
+                            },
+                        ),
+                        (
+                            "create-branch".to_string(),
+                            format!("Create release branch `{branch}`"),
+                            vec![],
+                            Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::CreateBranch {
+                                    branch: branch.clone(),
+                                    base,
+                                },
+                            },
+                        ),
+                        (
+                            "bump-version".to_string(),
+                            if workspace_version {
+                                format!(
+                                    "Bump version of package `{package}` to {version} in \
+                                     `[workspace.package]`"
+                                )
+                            } else {
+                                format!("Bump version of package `{package}` to {version}")
+                            },
+                            vec![Condition::WorkingTreeClean],
+                            Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::CommitVersionBump {
+                                    crate_name: package.clone(),
+                                    new_version: version.clone(),
+                                    manifest: if workspace_version {
+                                        root_manifest
+                                    } else {
+                                        manifest
+                                    },
+                                    workspace_version,
+                                    changelog,
+                                    date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+                                },
+                            },
+                        ),
+                        (
+                            "push-branch".to_string(),
+                            format!("Push branch `{branch}` to `{fork}`"),
+                            vec![],
+                            Action::Local {
+                                repository: repository.clone(),
+                                inner: LocalAction::PushBranch {
+                                    branch: branch.clone(),
+                                    remote: fork.clone(),
+                                },
+                            },
+                        ),
+                        (
+                            "release-pr".to_string(),
+                            format!("Open release PR for package `{package}` v{version}"),
+                            vec![],
+                            Action::Github {
+                                repository: repository.clone(),
+                                inner: GithubAction::CreateReleasePR {
+                                    crate_name: package.clone(),
+                                    new_version: version.clone(),
+                                    body,
+                                    head: Some(format!("{fork_owner}:{branch}")),
+                                },
+                            },
+                        ),
+                    ];
+                    items.splice(
+                        2..2,
+                        dependency_updates.into_iter().map(
+                            |(dependency, dependency_version, manifest, inherited)| {
+                                (
+                                    format!("update-{dependency}"),
+                                    if inherited {
+                                        format!(
+                                            "Require `{dependency}` v{dependency_version} in \
+                                             `[workspace.dependencies]`"
+                                        )
+                                    } else {
+                                        format!(
+                                            "Require `{dependency}` v{dependency_version} in \
+                                             package `{package}`"
+                                        )
+                                    },
+                                    vec![Condition::WorkingTreeClean],
+                                    Action::Local {
+                                        repository: repository.clone(),
+                                        inner: LocalAction::CommitDependencyUpdate {
+                                            crate_name: package.clone(),
+                                            dependency_name: dependency,
+                                            new_version: dependency_version,
+                                            manifest,
+                                        },
+                                    },
+                                )
+                            },
+                        ),
+                    );
+                    let mut previous = None;
+                    for (id, description, preconditions, action) in items {
+                        plan_actions.push(PlanItem {
+                            id: Some(id.clone()),
+                            description: Some(description),
+                            depends_on: Some(previous.into_iter().collect()),
+                            preconditions,
+                            action,
+                            postconditions: vec![],
                             continue_on_error: false,
                         });
+                        previous = Some(id);
                     }
                 }
             }
             if let Some((mut writer, path)) = json_plan_output {
                 log::info!("Serializing plan to `{}`...", path.display());
-                serde_json::to_writer_pretty(&mut writer, &Plan {
-                    items: plan_actions,
-                })
-                .expect("Could not serialize plan to file");
+                serde_json::to_writer_pretty(&mut writer, &Plan::new(plan_actions))
+                    .expect("Could not serialize plan to file");
                 log::info!("Wrote plan to `{}`.", path.display());
             }
         }
         Command::ExecuteActionPlan {
             json_plan_input,
             shellscript_output,
+            workflow_output,
             dry_run,
+            interactive,
+            jobs,
+            variables,
+            journal,
+            resume,
+            rollback,
         } => {
-            let plan: Plan = if let Some(path) = json_plan_input {
-                log::info!("Reading action plan from `{}`...", path.display());
-                let file = std::fs::File::open(path).unwrap();
-                let reader = std::io::BufReader::new(file);
-                serde_json::from_reader(reader).unwrap()
-            } else {
-                log::info!("Reading action plan from stdin...");
-                serde_json::from_reader(std::io::stdin().lock()).unwrap()
+            let journal_path =
+                journal.or_else(|| json_plan_input.as_deref().map(Journal::default_path));
+            let mut plan = match read_plan(json_plan_input.as_deref(), &variables) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    log::error!("Could not read action plan: {err}");
+                    std::process::exit(1);
+                }
             };
 
             log::debug!("Parsed action plan: {plan:?}");
 
-            if let Some(shellscript_output) = shellscript_output {
-                use std::io::Write;
+            let graph = match PlanGraph::new(&plan) {
+                Ok(graph) => graph,
+                Err(err) => {
+                    log::error!("Invalid action plan: {err}");
+                    std::process::exit(1);
+                }
+            };
 
-                let mut f = std::fs::File::create(&shellscript_output).unwrap();
+            if rollback {
+                let Some(path) = journal_path else {
+                    log::error!(
+                        "Cannot roll back without a journal, pass `--journal` or read the plan \
+                         from a file."
+                    );
+                    std::process::exit(1);
+                };
+                let mut journal = match Journal::open(&path, true) {
+                    Ok(journal) if !journal.entries().is_empty() => journal,
+                    Ok(_) => {
+                        log::error!("Journal `{}` has no entries.", path.display());
+                        std::process::exit(1);
+                    }
+                    Err(err) => {
+                        log::error!("Could not open journal: {err}");
+                        std::process::exit(1);
+                    }
+                };
+                if let Err(err) = plan::rollback(&plan, &mut journal, dry_run, &mut context).await {
+                    log::error!("Rollback failed: {err}");
+                    std::process::exit(1);
+                }
+            } else if let Some(shellscript_output) = shellscript_output {
                 log::info!(
                     "Serializing plan to shell script `{}`...",
                     shellscript_output.display()
                 );
-                writeln!(&mut f, "#!/bin/sh\n\nset -ev\n").unwrap();
-                // Do not repeat redundant directory changes
-                let mut prev_cd = None;
-                for plan_item in plan.items {
-                    if let Some(ref description) = plan_item.description {
-                        writeln!(&mut f, "# {description}").unwrap();
-                    }
-                    let Some(cmds) = plan_item
-                        .action
-                        .as_shell_command(plan_item.continue_on_error, &mut context)
-                    else {
-                        log::error!(
-                            "Can not represent plan item {plan_item:?} as shell script, aborting."
-                        );
-                        return;
-                    };
-                    for cmd in cmds {
-                        if matches!(prev_cd.as_ref(), Some(prev) if prev == &cmd) {
-                            continue;
-                        }
-                        writeln!(&mut f, "{cmd}").unwrap();
-                        if cmd.starts_with("cd ") {
-                            prev_cd = Some(cmd);
-                        }
+                match plan::to_shell_script(&plan, &graph, &mut context) {
+                    Ok(script) => std::fs::write(&shellscript_output, script).unwrap(),
+                    Err(err) => {
+                        log::error!("Could not export shell script: {err}");
+                        std::process::exit(1);
                     }
                 }
-                f.flush().unwrap();
-                drop(f);
                 log::info!(
                     "Wrote plan shell script to `{}`.",
                     shellscript_output.display()
                 );
-            } else if !dry_run {
-                for plan_item in plan.items {
-                    if let Some(ref description) = plan_item.description {
-                        log::info!("Executing `{description}`...");
-                    } else {
-                        log::info!("Executing `{:?}`...", plan_item.action);
+            } else if let Some(workflow_output) = workflow_output {
+                log::info!(
+                    "Serializing plan to workflow `{}`...",
+                    workflow_output.display()
+                );
+                let name = workflow_output
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "release".to_string());
+                let yaml = workflow::render(&plan, &graph, &name, &mut context)
+                    .and_then(|workflow| workflow::to_yaml(&workflow));
+                match yaml {
+                    Ok(yaml) => std::fs::write(&workflow_output, yaml).unwrap(),
+                    Err(err) => {
+                        log::error!("Could not export workflow: {err}");
+                        std::process::exit(1);
                     }
-                    let result = plan_item.action.execute(&mut context).await;
-                    if result.is_err() && !plan_item.continue_on_error {
-                        result.unwrap();
-                    } else if let Err(err) = result {
-                        log::error!("Action failed: {err}");
-                        log::info!("Continuing");
+                }
+                log::info!("Wrote plan workflow to `{}`.", workflow_output.display());
+            } else if dry_run {
+                let mut edited_files = EditedFiles::new();
+                for &i in &graph.order {
+                    println!(
+                        "{}",
+                        plan::preview(&plan.items[i], i, &mut edited_files, &mut context)
+                    );
+                }
+            } else {
+                let mut journal = match journal_path {
+                    Some(path) => match Journal::open(&path, resume) {
+                        Ok(journal) => {
+                            log::info!("Recording progress to journal `{}`.", path.display());
+                            Some(journal)
+                        }
+                        Err(err) => {
+                            log::error!("Could not open journal: {err}");
+                            std::process::exit(1);
+                        }
+                    },
+                    None if resume => {
+                        log::error!(
+                            "Cannot resume without a journal, pass `--journal` or read the plan \
+                             from a file."
+                        );
+                        std::process::exit(1);
                     }
-                    if let Some(ref description) = plan_item.description {
-                        log::info!("Executed `{description}`.");
-                    } else {
-                        log::info!("Executed `{:?}`.", plan_item.action);
+                    None => None,
+                };
+                let mut confirm = confirm_item;
+                let confirm: Option<&mut ConfirmFn> = if interactive {
+                    Some(&mut confirm)
+                } else {
+                    None
+                };
+                let outcomes =
+                    match plan::execute(&mut plan, journal.as_mut(), confirm, jobs, &mut context)
+                        .await
+                    {
+                        Ok(outcomes) => outcomes,
+                        Err(err) => {
+                            log::error!("Could not execute action plan: {err}");
+                            std::process::exit(1);
+                        }
+                    };
+                let mut failed = false;
+                for (i, outcome) in outcomes.iter().enumerate() {
+                    let label = plan.items[i].label(i);
+                    match outcome {
+                        ItemOutcome::Succeeded => {}
+                        ItemOutcome::Failed { error } => {
+                            failed |= !plan.items[i].continue_on_error;
+                            log::error!("Failed: {label}: {error}");
+                        }
+                        ItemOutcome::Skipped { .. } => {
+                            log::error!("Skipped: {label}");
+                        }
+                        ItemOutcome::Declined => {
+                            log::warn!("Skipped by request: {label}");
+                        }
                     }
                 }
+                if failed {
+                    std::process::exit(1);
+                }
             }
         }
+        Command::ValidatePlan {
+            json_plan_input,
+            variables,
+        } => {
+            let plan = match read_plan(json_plan_input.as_deref(), &variables) {
+                Ok(plan) => plan,
+                Err(err) => {
+                    log::error!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            if let Err(err) = PlanGraph::new(&plan) {
+                log::error!("Invalid action plan: {err}");
+                std::process::exit(1);
+            }
+            log::info!("Action plan with {} items is valid.", plan.items.len());
+        }
+        Command::PlanSchema { output } => {
+            let schema = plan::schema();
+            if let Some(path) = output {
+                let mut writer = std::fs::File::create(&path).unwrap();
+                serde_json::to_writer_pretty(&mut writer, &schema)
+                    .expect("Could not serialize schema to file");
+                log::info!("Wrote plan schema to `{}`.", path.display());
+            } else {
+                serde_json::to_writer_pretty(std::io::stdout().lock(), &schema)
+                    .expect("Could not serialize schema");
+                println!();
+            }
+        }
+    }
+}
+
+/// Changelog section of the crate's current version, if there is one.
+fn release_notes(c: &utilities::repository::Crate, repository: &std::path::Path) -> Option<String> {
+    let name = &c.package.name;
+    let version = &c.package.version;
+    let crate_dir = c.package.dir();
+    let Some(path) = changelog::find(crate_dir, repository) else {
+        log::warn!("No CHANGELOG.md found for package {name:?}, release will have no notes.");
+        return None;
+    };
+    let parsed = std::fs::read_to_string(&path)
+        .map_err(Into::into)
+        .and_then(|contents| changelog::Changelog::parse(&contents));
+    match parsed {
+        Ok(parsed) => match parsed.release(version).filter(|r| !r.is_empty()) {
+            Some(release) => Some(release.notes()),
+            None => {
+                log::warn!(
+                    "`{}` has no section for v{version}, release will have no notes.",
+                    path.display()
+                );
+                None
+            }
+        },
+        Err(err) => {
+            log::warn!("Could not parse `{}`: {err}", path.display());
+            None
+        }
+    }
+}
+
+fn read_plan(
+    json_plan_input: Option<&std::path::Path>,
+    variables: &PlanVariables,
+) -> Result<Plan, Box<dyn std::error::Error>> {
+    let variables = plan::variables(variables.vars.as_deref(), &variables.set)?;
+    if let Some(path) = json_plan_input {
+        log::info!("Reading action plan from `{}`...", path.display());
+        let file = std::fs::File::open(path)?;
+        plan::from_reader(std::io::BufReader::new(file), &variables)
+    } else {
+        log::info!("Reading action plan from stdin...");
+        plan::from_reader(std::io::stdin().lock(), &variables)
+    }
+}
+
+/// Show plan item and ask the user what to do with it.
+fn confirm_item(index: usize, plan_item: &mut PlanItem, context: &mut Context) -> Confirmation {
+    loop {
+        // Earlier items have been executed, so their edits are on disk.
+        eprintln!(
+            "\n{}",
+            plan::preview(plan_item, index, &mut EditedFiles::new(), context)
+        );
+        eprint!("[r]un, [s]kip, [a]bort or [e]dit? ");
+        let mut answer = String::new();
+        if !matches!(std::io::stdin().read_line(&mut answer), Ok(n) if n > 0) {
+            return Confirmation::Abort;
+        }
+        match answer.trim() {
+            "r" | "run" => return Confirmation::Run,
+            "s" | "skip" => return Confirmation::Skip,
+            "a" | "abort" => return Confirmation::Abort,
+            "e" | "edit" => {
+                if let Err(err) = edit_item(plan_item) {
+                    eprintln!("Could not edit plan item: {err}");
+                }
+            }
+            other => eprintln!("Unknown answer `{other}`."),
+        }
+    }
+}
+
+/// Open plan item as JSON in `$VISUAL` or `$EDITOR` and replace it with the
+/// result. Its `id` and `dependsOn` fields are left unchanged.
+fn edit_item(plan_item: &mut PlanItem) -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!(
+        "rust-vmm-helper-cli-item-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, serde_json::to_string_pretty(plan_item)?)?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut editor = editor.split_whitespace();
+    let status = std::process::Command::new(editor.next().unwrap_or("vi"))
+        .args(editor)
+        .arg(&path)
+        .status()?;
+    let edited = std::fs::read_to_string(&path);
+    _ = std::fs::remove_file(&path);
+    if !status.success() {
+        return Err(format!("Editor exited with {status}").into());
     }
+    let edited = edited?;
+    let de = &mut serde_json::Deserializer::from_str(&edited);
+    let mut edited: PlanItem = serde_path_to_error::deserialize(de)
+        .map_err(|err| format!("Invalid plan item at `{}`: {}", err.path(), err.inner()))?;
+    edited.id = plan_item.id.clone();
+    edited.depends_on = plan_item.depends_on.clone();
+    *plan_item = edited;
+    Ok(())
 }
@@ -0,0 +1,406 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Format preserving edits of `Cargo.toml` manifests.
+
+use std::path::PathBuf;
+
+/// Kinds of dependency tables, which may also appear under
+/// `[target.'cfg(..)']`.
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "build-dependencies", "dev-dependencies"];
+
+/// A dependency with a `path` source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDependency {
+    /// Key of the dependency, which differs from its package name if renamed.
+    pub name: String,
+    pub package: String,
+    /// Table it appears in, e.g. `dependencies` or
+    /// `target.'cfg(unix)'.dependencies`.
+    pub table: String,
+    pub path: PathBuf,
+    /// Version requirement, if any.
+    pub version: Option<String>,
+}
+
+impl PathDependency {
+    /// Development dependencies are not part of published crates, so they
+    /// only need a version if one is given.
+    pub fn is_dev(&self) -> bool {
+        self.table.ends_with("dev-dependencies")
+    }
+}
+
+/// Call `f` with the name and contents of every dependency table in
+/// `document`, including `[workspace.dependencies]`.
+fn for_each_dependency_table(
+    document: &mut toml_edit::DocumentMut,
+    mut f: impl FnMut(&str, &mut dyn toml_edit::TableLike),
+) {
+    for kind in DEPENDENCY_TABLES {
+        if let Some(table) = document
+            .get_mut(kind)
+            .and_then(toml_edit::Item::as_table_like_mut)
+        {
+            f(kind, table);
+        }
+    }
+    if let Some(table) = document
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(toml_edit::Item::as_table_like_mut)
+    {
+        f("workspace.dependencies", table);
+    }
+    let Some(targets) = document
+        .get_mut("target")
+        .and_then(toml_edit::Item::as_table_like_mut)
+    else {
+        return;
+    };
+    for (target, item) in targets.iter_mut() {
+        let Some(target_table) = item.as_table_like_mut() else {
+            continue;
+        };
+        for kind in DEPENDENCY_TABLES {
+            if let Some(table) = target_table
+                .get_mut(kind)
+                .and_then(toml_edit::Item::as_table_like_mut)
+            {
+                f(&format!("target.'{}'.{kind}", target.get()), table);
+            }
+        }
+    }
+}
+
+/// All dependencies with a `path` in manifest `contents`.
+pub fn path_dependencies(
+    contents: &str,
+) -> Result<Vec<PathDependency>, Box<dyn std::error::Error>> {
+    let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+    let mut dependencies = vec![];
+    for_each_dependency_table(&mut document, |table_name, table| {
+        for (name, item) in table.iter() {
+            let Some(dependency) = item.as_table_like() else {
+                continue;
+            };
+            let Some(path) = dependency.get("path").and_then(toml_edit::Item::as_str) else {
+                continue;
+            };
+            let field = |key| {
+                dependency
+                    .get(key)
+                    .and_then(toml_edit::Item::as_str)
+                    .map(str::to_string)
+            };
+            dependencies.push(PathDependency {
+                name: name.to_string(),
+                package: field("package").unwrap_or_else(|| name.to_string()),
+                table: table_name.to_string(),
+                path: PathBuf::from(path),
+                version: field("version"),
+            });
+        }
+    });
+    Ok(dependencies)
+}
+
+/// Set the version requirement of path dependency `name` to `version` in all
+/// tables it appears in, except for development dependencies without a
+/// version.
+pub fn set_dependency_version(
+    contents: &str,
+    name: &str,
+    version: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+    let mut found = false;
+    for_each_dependency_table(&mut document, |table_name, table| {
+        let Some(item) = table.get_mut(name) else {
+            return;
+        };
+        let Some(dependency) = item.as_table_like_mut() else {
+            return;
+        };
+        if !dependency.contains_key("path") {
+            return;
+        }
+        match dependency
+            .get_mut("version")
+            .and_then(toml_edit::Item::as_value_mut)
+        {
+            Some(value) => {
+                let decor = value.decor().clone();
+                *value = toml_edit::Value::from(version);
+                *value.decor_mut() = decor;
+            }
+            None if table_name.ends_with("dev-dependencies") => return,
+            None => {
+                dependency.insert("version", toml_edit::value(version));
+                // Spacing around inline table entries is kept per entry, which would leave
+                // the new one misplaced.
+                if let Some(inline) = item.as_inline_table_mut() {
+                    inline.fmt();
+                }
+            }
+        }
+        found = true;
+    });
+    if !found {
+        return Err(format!("Manifest has no path dependency `{name}`").into());
+    }
+    Ok(document.to_string())
+}
+
+/// `package.version` of manifest `contents`.
+pub fn package_version(contents: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let document = contents.parse::<toml_edit::DocumentMut>()?;
+    document
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(toml_edit::Item::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Manifest has no `package.version` string".into())
+}
+
+/// Whether dependency `name` is declared with `workspace = true` in
+/// manifest `contents`, so that its source and version are in the workspace
+/// root's `[workspace.dependencies]`.
+pub fn inherits_dependency(contents: &str, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+    let mut inherited = false;
+    for_each_dependency_table(&mut document, |_, table| {
+        inherited |= table
+            .get(name)
+            .and_then(|item| item.get("workspace"))
+            .and_then(toml_edit::Item::as_bool)
+            == Some(true);
+    });
+    Ok(inherited)
+}
+
+/// `[workspace.package].version` of workspace root manifest `contents`.
+pub fn workspace_package_version(contents: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let document = contents.parse::<toml_edit::DocumentMut>()?;
+    document
+        .get("workspace")
+        .and_then(|workspace| workspace.get("package"))
+        .and_then(|package| package.get("version"))
+        .and_then(toml_edit::Item::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Manifest has no `workspace.package.version` string".into())
+}
+
+/// Whether manifest `contents` inherits its version from the workspace with
+/// `version.workspace = true`.
+pub fn inherits_package_version(contents: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let document = contents.parse::<toml_edit::DocumentMut>()?;
+    Ok(document
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(|version| version.get("workspace"))
+        .and_then(toml_edit::Item::as_bool)
+        == Some(true))
+}
+
+/// Set `package.version` of manifest `contents`, leaving everything else
+/// untouched.
+pub fn set_package_version(
+    contents: &str,
+    version: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+    let Some(package) = document
+        .get_mut("package")
+        .and_then(toml_edit::Item::as_table_like_mut)
+    else {
+        return Err("Manifest has no `[package]` table".into());
+    };
+    set_version(package, version, "package.version")?;
+    Ok(document.to_string())
+}
+
+/// Set `[workspace.package].version` of workspace root manifest `contents`,
+/// which members inherit with `version.workspace = true`.
+pub fn set_workspace_package_version(
+    contents: &str,
+    version: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+    let Some(package) = document
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("package"))
+        .and_then(toml_edit::Item::as_table_like_mut)
+    else {
+        return Err("Manifest has no `[workspace.package]` table".into());
+    };
+    set_version(package, version, "workspace.package.version")?;
+    Ok(document.to_string())
+}
+
+/// Replace the `version` string of `table`, whose full name is `field`.
+fn set_version(
+    table: &mut dyn toml_edit::TableLike,
+    version: &str,
+    field: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(item) = table.get_mut("version") else {
+        return Err(format!("Manifest has no `{field}` field").into());
+    };
+    // Either `version = { workspace = true }` or `version.workspace = true`.
+    if item.get("workspace").is_some() {
+        return Err(format!("`{field}` is inherited from the workspace").into());
+    }
+    let Some(value) = item.as_value_mut() else {
+        return Err(format!("`{field}` is not a value").into());
+    };
+    // Keep comments and whitespace around the old value.
+    let decor = value.decor().clone();
+    *value = toml_edit::Value::from(version);
+    *value.decor_mut() = decor;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_dependency_version() {
+        let before = r#"[package]
+name = "vm-fixture"
+version = "0.1.0"
+
+[dependencies]
+vm-dep = { path = "../vm-dep", version = "0.1.0" } # inline
+vm-other = { path = "../vm-other", version = "0.1.0" }
+vm-unversioned = { path = "../vm-dep", package = "vm-dep" }
+
+[build-dependencies.vm-dep]
+path = "../vm-dep"
+# Comment above the version.
+version   =   "0.1.0"   # after the version
+
+[dev-dependencies]
+vm-dep = { path = "../vm-dep" }
+
+[target.'cfg(unix)'.dependencies]
+vm-dep = { path = "../vm-dep", version = "=0.1.0", features = ["unix"] }
+
+[workspace.dependencies]
+vm-dep.path = "../vm-dep"
+vm-dep.version = "0.1"
+"#;
+        let after = set_dependency_version(before, "vm-dep", "0.2.0").unwrap();
+        assert_eq!(
+            after,
+            r#"[package]
+name = "vm-fixture"
+version = "0.1.0"
+
+[dependencies]
+vm-dep = { path = "../vm-dep", version = "0.2.0" } # inline
+vm-other = { path = "../vm-other", version = "0.1.0" }
+vm-unversioned = { path = "../vm-dep", package = "vm-dep" }
+
+[build-dependencies.vm-dep]
+path = "../vm-dep"
+# Comment above the version.
+version   =   "0.2.0"   # after the version
+
+[dev-dependencies]
+vm-dep = { path = "../vm-dep" }
+
+[target.'cfg(unix)'.dependencies]
+vm-dep = { path = "../vm-dep", version = "0.2.0", features = ["unix"] }
+
+[workspace.dependencies]
+vm-dep.path = "../vm-dep"
+vm-dep.version = "0.2.0"
+"#
+        );
+
+        // Renamed dependencies are found by key, and missing versions are added.
+        let after = set_dependency_version(before, "vm-unversioned", "0.2.0").unwrap();
+        assert!(
+            after.contains(
+                "vm-unversioned = { path = \"../vm-dep\", package = \"vm-dep\", version = \
+                 \"0.2.0\" }\n"
+            ),
+            "{after}"
+        );
+        let err = set_dependency_version(before, "vm-fixture", "0.2.0")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Manifest has no path dependency `vm-fixture`");
+    }
+
+    #[test]
+    fn test_set_package_version() {
+        let before = "[package]\nname = \"vm-fixture\"\n# Bumped on release.\nversion = \"0.1.0\" \
+                      # comment\nedition = \"2024\"\n";
+        let after = set_package_version(before, "0.2.0").unwrap();
+        assert_eq!(after, before.replace("\"0.1.0\"", "\"0.2.0\""));
+        assert_eq!(package_version(&after).unwrap(), "0.2.0");
+        assert!(!inherits_package_version(&after).unwrap());
+
+        let inherited = "[package]\nname = \"vm-fixture\"\nversion.workspace = true\n";
+        assert!(inherits_package_version(inherited).unwrap());
+        let err = set_package_version(inherited, "0.2.0")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "`package.version` is inherited from the workspace");
+        let inherited = "[package]\nname = \"vm-fixture\"\nversion = { workspace = true }\n";
+        assert!(inherits_package_version(inherited).unwrap());
+
+        let err = set_package_version("[workspace]\n", "0.2.0")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Manifest has no `[package]` table");
+        let err = set_package_version("[package]\nname = \"vm-fixture\"\n", "0.2.0")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Manifest has no `package.version` field");
+    }
+
+    #[test]
+    fn test_set_workspace_package_version() {
+        let before = "[workspace]\nmembers = [\"crates/*\"]\n\n[workspace.package]\nversion = \
+                      \"0.1.0\" # shared\nedition = \"2024\"\n";
+        let after = set_workspace_package_version(before, "0.2.0").unwrap();
+        assert_eq!(after, before.replace("\"0.1.0\"", "\"0.2.0\""));
+        assert_eq!(workspace_package_version(&after).unwrap(), "0.2.0");
+        // Members inherit the version, the root does not have one of its own.
+        assert!(!inherits_package_version(&after).unwrap());
+        assert!(package_version(&after).is_err());
+
+        let err = set_workspace_package_version("[workspace]\n", "0.2.0")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Manifest has no `[workspace.package]` table");
+        let err =
+            set_workspace_package_version("[workspace.package]\nedition = \"2024\"\n", "0.2.0")
+                .unwrap_err()
+                .to_string();
+        assert_eq!(err, "Manifest has no `workspace.package.version` field");
+    }
+}
@@ -0,0 +1,1372 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, Compensation, Plan, PlanItem},
+    logging,
+    steps::EditedFiles,
+};
+
+/// Values of `${name}` placeholders in plan templates.
+pub type Variables = IndexMap<String, serde_json::Value>;
+
+/// Parse a plan, migrating it from older format versions and substituting
+/// `variables` into it first.
+///
+/// Errors point to the exact location of the offending value in the plan.
+pub fn from_reader<R: std::io::Read>(
+    reader: R,
+    variables: &Variables,
+) -> Result<Plan, Box<dyn std::error::Error>> {
+    let mut value: serde_json::Value =
+        serde_json::from_reader(reader).map_err(|err| format!("Invalid JSON: {err}"))?;
+    migrate(&mut value)?;
+    if let Some(items) = value.get_mut("items") {
+        let mut unresolved = vec![];
+        substitute(items, variables, "items", &mut unresolved)?;
+        if !unresolved.is_empty() {
+            return Err(format!("Unresolved plan variables: {}", unresolved.join(", ")).into());
+        }
+    }
+    serde_path_to_error::deserialize(value)
+        .map_err(|err| format!("Invalid plan at `{}`: {}", err.path(), err.inner()).into())
+}
+
+/// Upgrade a serialized plan to [`Plan::FORMAT_VERSION`] in place.
+pub fn migrate(value: &mut serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(object) = value.as_object_mut() else {
+        return Err("Invalid plan: expected a JSON object.".into());
+    };
+    let version = match object.get("formatVersion") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or("Invalid plan at `formatVersion`: expected an unsigned integer.")?,
+    };
+    if version > u64::from(Plan::FORMAT_VERSION) {
+        return Err(format!(
+            "Plan format version {version} is newer than the latest supported version {}, please \
+             upgrade.",
+            Plan::FORMAT_VERSION
+        )
+        .into());
+    }
+    if version == 0 {
+        // Version 0 plans predate item ids, dependencies and conditions, and their
+        // items were executed in order, which is what items without `dependsOn`
+        // still do.
+        object.insert("formatVersion".to_string(), 1.into());
+    }
+    if version < u64::from(Plan::FORMAT_VERSION) {
+        log::info!(
+            "Migrated plan from format version {version} to {}.",
+            Plan::FORMAT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Replace `${name}` placeholders in every string of `value` with the value of
+/// variable `name`. `$${` is an escaped, literal `${`.
+///
+/// A string that consists of a single placeholder is replaced by the
+/// variable's value as is, so it can be of any type; the plan's deserialization
+/// then checks that the type fits. Placeholders inside longer strings must
+/// refer to strings, numbers or booleans. Placeholders without a value are
+/// appended to `unresolved` along with their location.
+pub fn substitute(
+    value: &mut serde_json::Value,
+    variables: &Variables,
+    path: &str,
+    unresolved: &mut Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use serde_json::Value;
+
+    match value {
+        Value::Array(array) => {
+            for (i, value) in array.iter_mut().enumerate() {
+                substitute(value, variables, &format!("{path}[{i}]"), unresolved)?;
+            }
+        }
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                substitute(value, variables, &format!("{path}.{key}"), unresolved)?;
+            }
+        }
+        Value::String(string) => {
+            if let Some(name) = string
+                .strip_prefix("${")
+                .and_then(|s| s.strip_suffix('}'))
+                .filter(|name| is_variable_name(name))
+            {
+                match variables.get(name) {
+                    Some(var) => *value = var.clone(),
+                    None => unresolved.push(format!("`{name}` at `{path}`")),
+                }
+                return Ok(());
+            }
+            let mut result = String::with_capacity(string.len());
+            let mut rest = string.as_str();
+            while let Some(pos) = rest.find('$') {
+                result.push_str(&rest[..pos]);
+                rest = &rest[pos..];
+                if let Some(escaped) = rest.strip_prefix("$${") {
+                    result.push_str("${");
+                    rest = escaped;
+                    continue;
+                }
+                let Some((name, after)) = rest
+                    .strip_prefix("${")
+                    .and_then(|s| s.split_once('}'))
+                    .filter(|(name, _)| is_variable_name(name))
+                else {
+                    result.push('$');
+                    rest = &rest[1..];
+                    continue;
+                };
+                match variables.get(name) {
+                    Some(Value::String(var)) => result.push_str(var),
+                    Some(var @ (Value::Number(_) | Value::Bool(_))) => {
+                        result.push_str(&var.to_string())
+                    }
+                    Some(_) => {
+                        return Err(format!(
+                            "Plan variable `{name}` at `{path}` is not a string, number or \
+                             boolean and can not be part of a string."
+                        )
+                        .into());
+                    }
+                    None => unresolved.push(format!("`{name}` at `{path}`")),
+                }
+                rest = after;
+            }
+            result.push_str(rest);
+            *string = result;
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Collect plan variables from a TOML file and `KEY=VALUE` assignments, the
+/// latter taking precedence.
+///
+/// Values of assignments are strings, except for `true` and `false`; use a
+/// variables file for other types.
+pub fn variables(
+    vars_file: Option<&Path>,
+    assignments: &[String],
+) -> Result<Variables, Box<dyn std::error::Error>> {
+    let mut variables = Variables::new();
+    if let Some(path) = vars_file {
+        let table: toml::value::Table = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|err| format!("Could not parse `{}`: {err}", path.display()))?;
+        for (name, value) in table {
+            variables.insert(name, serde_json::to_value(value)?);
+        }
+    }
+    for assignment in assignments {
+        let Some((name, value)) = assignment.split_once('=') else {
+            return Err(format!("Expected `KEY=VALUE`, got `{assignment}`.").into());
+        };
+        let value = match value {
+            "true" => serde_json::Value::Bool(true),
+            "false" => serde_json::Value::Bool(false),
+            other => serde_json::Value::String(other.to_string()),
+        };
+        variables.insert(name.to_string(), value);
+    }
+    for name in variables.keys() {
+        if !is_variable_name(name) {
+            return Err(format!(
+                "Invalid plan variable name `{name}`, only ASCII alphanumerics, `_`, `-` and `.` \
+                 are allowed."
+            )
+            .into());
+        }
+    }
+    Ok(variables)
+}
+
+/// JSON Schema of the plan file format.
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(Plan)
+}
+
+/// Dependency graph of a [`Plan`]'s items, indexed by their position in
+/// [`Plan::items`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanGraph {
+    /// `dependencies[i]` holds the items that item `i` depends on.
+    pub dependencies: Vec<Vec<usize>>,
+    /// All items in topological order. Ties are broken by position in the
+    /// plan, so a plan without explicit dependencies keeps its order.
+    pub order: Vec<usize>,
+}
+
+impl PlanGraph {
+    /// Build and validate the dependency graph of `plan`.
+    ///
+    /// Fails on duplicate ids, dependencies on ids that do not exist and
+    /// dependency cycles.
+    pub fn new(plan: &Plan) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        for (i, item) in plan.items.iter().enumerate() {
+            if let Some(ref id) = item.id
+                && let Some(prev) = ids.insert(id.as_str(), i)
+            {
+                return Err(format!(
+                    "Plan items #{prev} and #{i} have the same id `{id}`, ids must be unique."
+                )
+                .into());
+            }
+        }
+
+        let mut dependencies = Vec::with_capacity(plan.items.len());
+        for (i, item) in plan.items.iter().enumerate() {
+            let deps: Vec<usize> = match item.depends_on {
+                None => (0..i).collect(),
+                Some(ref depends_on) => {
+                    let mut deps = BTreeSet::new();
+                    for dep in depends_on {
+                        let Some(&dep_idx) = ids.get(dep.as_str()) else {
+                            return Err(format!(
+                                "Plan item {} depends on `{dep}` but no item has that id.",
+                                item.label(i)
+                            )
+                            .into());
+                        };
+                        if dep_idx == i {
+                            return Err(
+                                format!("Plan item {} depends on itself.", item.label(i)).into()
+                            );
+                        }
+                        deps.insert(dep_idx);
+                    }
+                    deps.into_iter().collect()
+                }
+            };
+            dependencies.push(deps);
+        }
+
+        let mut dependents = vec![vec![]; plan.items.len()];
+        let mut in_degree = vec![0_usize; plan.items.len()];
+        for (i, deps) in dependencies.iter().enumerate() {
+            in_degree[i] = deps.len();
+            for &dep in deps {
+                dependents[dep].push(i);
+            }
+        }
+        let mut ready: BTreeSet<usize> = (0..plan.items.len())
+            .filter(|&i| in_degree[i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(plan.items.len());
+        while let Some(i) = ready.pop_first() {
+            order.push(i);
+            for &dependent in &dependents[i] {
+                in_degree[dependent] -= 1;
+                if in_degree[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+        if order.len() != plan.items.len() {
+            let cycle = (0..plan.items.len())
+                .filter(|&i| in_degree[i] > 0)
+                .map(|i| plan.items[i].label(i))
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "Plan has a dependency cycle involving items {}.",
+                cycle.join(", ")
+            )
+            .into());
+        }
+
+        Ok(Self {
+            dependencies,
+            order,
+        })
+    }
+}
+
+/// Result of executing a single plan item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemOutcome {
+    Succeeded,
+    Failed {
+        error: String,
+    },
+    /// Not executed because a dependency did not succeed.
+    Skipped {
+        dependency: usize,
+    },
+    /// Not executed because the user chose to skip it.
+    Declined,
+}
+
+/// Decision on whether to execute a plan item, see [`execute`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Run,
+    Skip,
+    Abort,
+}
+
+/// Callback asked before executing each plan item. It may modify the item,
+/// except for its `id` and `depends_on` fields.
+pub type ConfirmFn<'a> = dyn FnMut(usize, &mut PlanItem, &mut crate::Context) -> Confirmation + 'a;
+
+/// Human readable rendering of a plan item, with the exact shell commands its
+/// action corresponds to. Files are edited on top of `edited_files`, see
+/// [`EditedFiles`].
+pub fn preview(
+    plan_item: &PlanItem,
+    index: usize,
+    edited_files: &mut EditedFiles,
+    context: &mut crate::Context,
+) -> String {
+    use std::fmt::Write;
+
+    let mut out = plan_item.label(index);
+    out.push('\n');
+    if let Some(ref depends_on) = plan_item.depends_on
+        && !depends_on.is_empty()
+    {
+        _ = writeln!(out, "  depends on: {}", depends_on.join(", "));
+    }
+    for condition in &plan_item.preconditions {
+        _ = writeln!(out, "  precondition: {condition}");
+    }
+    match plan_item
+        .action
+        .as_shell_command(plan_item.continue_on_error, edited_files, context)
+    {
+        Some(cmds) => {
+            _ = writeln!(out, "  commands:");
+            for cmd in cmds {
+                _ = writeln!(out, "    {cmd}");
+            }
+        }
+        None => {
+            _ = writeln!(out, "  action: {:?}", plan_item.action);
+        }
+    }
+    for condition in &plan_item.postconditions {
+        _ = writeln!(out, "  postcondition: {condition}");
+    }
+    if plan_item.continue_on_error {
+        _ = writeln!(out, "  errors do not halt execution");
+    }
+    if plan_item.action.compensation() == Compensation::Irreversible {
+        _ = writeln!(out, "  IRREVERSIBLE: can not be rolled back");
+    }
+    out
+}
+
+/// Render `plan` as a shell script, with items in the order of `graph`.
+pub fn to_shell_script(
+    plan: &Plan,
+    graph: &PlanGraph,
+    context: &mut crate::Context,
+) -> Result<String, Box<dyn std::error::Error>> {
+    use std::fmt::Write;
+
+    let mut out = String::from("#!/bin/sh\n\nset -ev\n\n");
+    // Do not repeat redundant directory changes
+    let mut prev_cd = None;
+    let mut edited_files = EditedFiles::new();
+    for &i in &graph.order {
+        let plan_item = &plan.items[i];
+        if let Some(ref description) = plan_item.description {
+            for line in description.lines() {
+                _ = writeln!(out, "# {line}");
+            }
+        }
+        let Some(cmds) = plan_item.as_shell_command(i, &mut edited_files, context) else {
+            return Err(format!(
+                "Can not represent plan item {} as shell script",
+                plan_item.label(i)
+            )
+            .into());
+        };
+        for cmd in cmds {
+            if matches!(prev_cd.as_ref(), Some(prev) if prev == &cmd) {
+                continue;
+            }
+            _ = writeln!(out, "{cmd}");
+            if cmd.starts_with("cd ") {
+                prev_cd = Some(cmd);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Journal record of a plan item's execution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    /// Position of item in the plan.
+    pub index: usize,
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The item's action, used to detect plans modified since the journal was
+    /// written. For [`JournalEvent::Edited`] it is the action before the edit.
+    pub action: Action,
+    pub event: JournalEvent,
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JournalEvent {
+    /// The item was modified before execution, see [`ConfirmFn`]. Later
+    /// entries of the item refer to the edited item.
+    Edited {
+        item: Box<PlanItem>,
+    },
+    Started,
+    Finished {
+        outcome: ItemOutcome,
+    },
+    /// The item's effect was reverted by [`rollback`].
+    RolledBack,
+}
+
+/// Append-only log of plan execution progress, stored as one JSON
+/// [`JournalEntry`] per line.
+///
+/// Every entry is synced to disk before the corresponding action runs (or
+/// after it finishes), so that an interrupted execution can be resumed
+/// without repeating completed items.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: std::fs::File,
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Journal location for a plan file, next to it.
+    pub fn default_path(plan_path: &Path) -> PathBuf {
+        let mut file_name = plan_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".journal");
+        plan_path.with_file_name(file_name)
+    }
+
+    /// Open journal at `path`.
+    ///
+    /// If `resume` is `true`, existing entries are loaded and new ones are
+    /// appended to them. Otherwise the journal must not already contain any
+    /// entries, to avoid accidentally re-executing a plan that has already
+    /// (partially) run.
+    pub fn open(path: &Path, resume: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut entries = vec![];
+        if path.exists() {
+            let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            for (lineno, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let de = &mut serde_json::Deserializer::from_str(&line);
+                entries.push(serde_path_to_error::deserialize(de).map_err(|err| {
+                    format!(
+                        "Could not parse journal `{}` line {}: {err}",
+                        path.display(),
+                        lineno + 1
+                    )
+                })?);
+            }
+        }
+        if !resume && !entries.is_empty() {
+            return Err(format!(
+                "Journal `{}` already exists and has entries; resume execution or remove it.",
+                path.display()
+            )
+            .into());
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Append an entry and sync it to disk.
+    pub fn record(
+        &mut self,
+        plan: &Plan,
+        index: usize,
+        event: JournalEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = JournalEntry {
+            index,
+            id: plan.items[index].id.clone(),
+            action: plan.items[index].action.clone(),
+            event,
+            timestamp,
+        };
+        serde_json::to_writer(&mut self.file, &entry)?;
+        self.file.write_all(b"\n")?;
+        self.file.sync_data()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Latest recorded event of every item, after checking that the journal
+    /// belongs to `plan`.
+    ///
+    /// [`JournalEvent::Edited`] entries are not events of their own, they
+    /// only replace the item later entries are checked against.
+    pub fn last_events(
+        &self,
+        plan: &Plan,
+    ) -> Result<HashMap<usize, JournalEvent>, Box<dyn std::error::Error>> {
+        let mut events = HashMap::new();
+        let mut edited = HashMap::new();
+        for entry in &self.entries {
+            let Some(plan_item) = edited
+                .get(&entry.index)
+                .copied()
+                .or_else(|| plan.items.get(entry.index))
+            else {
+                return Err(format!(
+                    "Journal `{}` refers to item #{} but plan only has {} items.",
+                    self.path.display(),
+                    entry.index,
+                    plan.items.len()
+                )
+                .into());
+            };
+            if plan_item.id != entry.id || plan_item.action != entry.action {
+                return Err(format!(
+                    "Journal `{}` does not match plan item {}, was the plan modified?",
+                    self.path.display(),
+                    plan_item.label(entry.index)
+                )
+                .into());
+            }
+            match entry.event {
+                JournalEvent::Edited { ref item } => {
+                    edited.insert(entry.index, item.as_ref());
+                }
+                ref event => {
+                    events.insert(entry.index, event.clone());
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Replace items of `plan` with their latest edit recorded in the journal.
+    pub fn apply_edits(&self, plan: &mut Plan) {
+        for entry in &self.entries {
+            if let JournalEvent::Edited { ref item } = entry.event
+                && let Some(plan_item) = plan.items.get_mut(entry.index)
+            {
+                *plan_item = item.as_ref().clone();
+            }
+        }
+    }
+}
+
+/// Execute a single item's action, checking its pre- and postconditions.
+async fn execute_item(
+    plan_item: &PlanItem,
+    context: &mut crate::Context,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(condition) = plan_item
+        .first_unmet(&plan_item.preconditions, context)
+        .await?
+    {
+        return Err(format!("Precondition not met: {condition}").into());
+    }
+    plan_item.action.execute(context).await?;
+    if let Some(condition) = plan_item
+        .first_unmet(&plan_item.postconditions, context)
+        .await?
+    {
+        return Err(format!("Postcondition not met: {condition}").into());
+    }
+    Ok(())
+}
+
+/// Execute `plan` in topological order, running up to `jobs` items without
+/// mutual dependencies concurrently.
+///
+/// When an item fails and its `continue_on_error` is not set, only the items
+/// that (transitively) depend on it are skipped; everything else still runs.
+/// The outcome of every item is returned, indexed by position in the plan.
+/// With more than one job, the log output of each item is held back until it
+/// finishes.
+///
+/// If a `journal` is given, progress is recorded in it. Items it already
+/// records as succeeded are not executed again, and items that were started
+/// but never finished are checked with [`Action::is_done`] first.
+///
+/// If `confirm` is given, it is asked before executing each item and items
+/// are executed one at a time. Skipped items block their dependents, and
+/// aborting stops execution with an error. Items it modifies are recorded in
+/// the journal, and resuming executes them as modified.
+pub async fn execute(
+    plan: &mut Plan,
+    mut journal: Option<&mut Journal>,
+    mut confirm: Option<&mut ConfirmFn<'_>>,
+    jobs: usize,
+    context: &mut crate::Context,
+) -> Result<Vec<ItemOutcome>, Box<dyn std::error::Error>> {
+    let graph = PlanGraph::new(plan)?;
+    let previous_events = match journal {
+        Some(ref journal) => {
+            let events = journal.last_events(plan)?;
+            journal.apply_edits(plan);
+            events
+        }
+        None => HashMap::new(),
+    };
+    let jobs = if confirm.is_some() { 1 } else { jobs.max(1) };
+    let mut outcomes: Vec<Option<ItemOutcome>> = vec![None; plan.items.len()];
+    let mut started = vec![false; plan.items.len()];
+    let mut running = tokio::task::JoinSet::new();
+    loop {
+        // Start items whose dependencies have finished, until all job slots
+        // are taken. Items that are not executed finish immediately and may
+        // make others ready, so repeat until nothing changes.
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &i in &graph.order {
+                if started[i]
+                    || running.len() >= jobs
+                    || graph.dependencies[i]
+                        .iter()
+                        .any(|&dep| outcomes[dep].is_none())
+                {
+                    continue;
+                }
+                started[i] = true;
+                changed = true;
+                let label = plan.items[i].label(i);
+                match previous_events.get(&i) {
+                    Some(JournalEvent::Finished {
+                        outcome: ItemOutcome::Succeeded,
+                    }) => {
+                        log::info!("Skipping {label}, it has already been executed.");
+                        outcomes[i] = Some(ItemOutcome::Succeeded);
+                        continue;
+                    }
+                    Some(JournalEvent::Started) => {
+                        log::warn!("Execution of {label} was interrupted, checking its status...");
+                        if plan.items[i].action.is_done(context).await? == Some(true) {
+                            log::info!("{label} had completed before the interruption.");
+                            if let Some(journal) = journal.as_deref_mut() {
+                                journal.record(
+                                    plan,
+                                    i,
+                                    JournalEvent::Finished {
+                                        outcome: ItemOutcome::Succeeded,
+                                    },
+                                )?;
+                            }
+                            outcomes[i] = Some(ItemOutcome::Succeeded);
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+                let blocking_dependency =
+                    graph.dependencies[i]
+                        .iter()
+                        .copied()
+                        .find(|&dep| match outcomes[dep] {
+                            Some(ItemOutcome::Succeeded) => false,
+                            Some(ItemOutcome::Failed { .. }) => !plan.items[dep].continue_on_error,
+                            Some(ItemOutcome::Skipped { .. } | ItemOutcome::Declined) | None => {
+                                true
+                            }
+                        });
+                let confirmation = match (blocking_dependency, confirm.as_deref_mut()) {
+                    (None, Some(confirm)) => {
+                        let mut plan_item = plan.items[i].clone();
+                        let confirmation = confirm(i, &mut plan_item, context);
+                        if plan_item != plan.items[i] {
+                            // Record the edit before the item is replaced, so that the journal
+                            // still matches the plan on disk when resuming.
+                            if let Some(journal) = journal.as_deref_mut() {
+                                journal.record(
+                                    plan,
+                                    i,
+                                    JournalEvent::Edited {
+                                        item: Box::new(plan_item.clone()),
+                                    },
+                                )?;
+                            }
+                            plan.items[i] = plan_item;
+                        }
+                        confirmation
+                    }
+                    _ => Confirmation::Run,
+                };
+                let outcome = if let Some(dependency) = blocking_dependency {
+                    log::warn!(
+                        "Skipping {label} because {} did not succeed.",
+                        plan.items[dependency].label(dependency)
+                    );
+                    ItemOutcome::Skipped { dependency }
+                } else if confirmation == Confirmation::Abort {
+                    return Err(format!("Execution aborted before {label}.").into());
+                } else if confirmation == Confirmation::Skip {
+                    log::info!("Skipping {label} as requested.");
+                    ItemOutcome::Declined
+                } else {
+                    log::info!("Executing {label}...");
+                    if let Some(journal) = journal.as_deref_mut() {
+                        journal.record(plan, i, JournalEvent::Started)?;
+                    }
+                    let plan_item = plan.items[i].clone();
+                    let mut item_context = context.clone();
+                    running.spawn(async move {
+                        let execution = async {
+                            execute_item(&plan_item, &mut item_context)
+                                .await
+                                .map_err(|err| err.to_string())
+                        };
+                        if jobs > 1 {
+                            let (result, lines) = logging::grouped(execution).await;
+                            (i, result, lines)
+                        } else {
+                            (i, execution.await, vec![])
+                        }
+                    });
+                    continue;
+                };
+                if let Some(journal) = journal.as_deref_mut() {
+                    journal.record(
+                        plan,
+                        i,
+                        JournalEvent::Finished {
+                            outcome: outcome.clone(),
+                        },
+                    )?;
+                }
+                outcomes[i] = Some(outcome);
+            }
+        }
+
+        let Some(finished) = running.join_next().await else {
+            break;
+        };
+        let (i, result, lines) = finished?;
+        logging::flush(lines);
+        let label = plan.items[i].label(i);
+        let outcome = match result {
+            Ok(()) => {
+                log::info!("Executed {label}.");
+                ItemOutcome::Succeeded
+            }
+            Err(error) => {
+                log::error!("Action {label} failed: {error}");
+                if plan.items[i].continue_on_error {
+                    log::info!("Continuing");
+                }
+                ItemOutcome::Failed { error }
+            }
+        };
+        if let Some(journal) = journal.as_deref_mut() {
+            journal.record(
+                plan,
+                i,
+                JournalEvent::Finished {
+                    outcome: outcome.clone(),
+                },
+            )?;
+        }
+        outcomes[i] = Some(outcome);
+    }
+    Ok(outcomes
+        .into_iter()
+        .map(|o| o.expect("every item is visited in topological order"))
+        .collect())
+}
+
+/// Revert the effects of every item `journal` records as succeeded, most
+/// recently finished first.
+///
+/// Stops at the first item whose effect is [`Compensation::Irreversible`] or
+/// whose compensation fails, leaving the remaining items in place. With
+/// `dry_run`, compensations are only reported.
+pub async fn rollback(
+    plan: &Plan,
+    journal: &mut Journal,
+    dry_run: bool,
+    context: &mut crate::Context,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let events = journal.last_events(plan)?;
+    let mut plan = plan.clone();
+    journal.apply_edits(&mut plan);
+    let plan = &plan;
+    let mut last_position = HashMap::new();
+    for (position, entry) in journal.entries().iter().enumerate() {
+        if !matches!(entry.event, JournalEvent::Edited { .. }) {
+            last_position.insert(entry.index, position);
+        }
+    }
+    let mut to_revert = vec![];
+    for (&i, event) in &events {
+        match event {
+            JournalEvent::Finished {
+                outcome: ItemOutcome::Succeeded,
+            } => to_revert.push(i),
+            JournalEvent::Started => {
+                let label = plan.items[i].label(i);
+                log::warn!("Execution of {label} was interrupted, checking its status...");
+                if plan.items[i].action.is_done(context).await? != Some(false) {
+                    to_revert.push(i);
+                }
+            }
+            JournalEvent::Finished { .. }
+            | JournalEvent::RolledBack
+            | JournalEvent::Edited { .. } => {}
+        }
+    }
+    to_revert.sort_by_key(|i| std::cmp::Reverse(last_position[i]));
+    if to_revert.is_empty() {
+        log::info!("Nothing to roll back.");
+    }
+
+    for i in to_revert {
+        let label = plan.items[i].label(i);
+        match plan.items[i].action.compensation() {
+            Compensation::None => {
+                log::info!("Nothing to roll back for {label}.");
+                if !dry_run {
+                    journal.record(plan, i, JournalEvent::RolledBack)?;
+                }
+            }
+            Compensation::Irreversible => {
+                return Err(format!(
+                    "{label} can not be rolled back, stopping. Items executed before it have not \
+                     been rolled back."
+                )
+                .into());
+            }
+            Compensation::Action(compensation) => {
+                if dry_run {
+                    log::info!("Would roll back {label} with {compensation:?}.");
+                    continue;
+                }
+                log::info!("Rolling back {label}...");
+                compensation
+                    .execute(context)
+                    .await
+                    .map_err(|err| format!("Could not roll back {label}: {err}"))?;
+                journal.record(plan, i, JournalEvent::RolledBack)?;
+                log::info!("Rolled back {label}.");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        actions::LocalAction,
+        test_utils::{git, repository, write_executable},
+    };
+
+    fn create_tag(tag: &str) -> PlanItem {
+        PlanItem {
+            id: Some("tag".to_string()),
+            description: None,
+            depends_on: None,
+            preconditions: vec![],
+            action: Action::Local {
+                repository: PathBuf::from("."),
+                inner: LocalAction::CreateTags {
+                    new_tags: vec![tag.to_string()],
+                    message: None,
+                },
+            },
+            postconditions: vec![],
+            continue_on_error: false,
+        }
+    }
+
+    /// Item tagging `repository` with its `id`.
+    fn tag_item(id: &str, depends_on: Option<&[&str]>, repository: &Path) -> PlanItem {
+        PlanItem {
+            id: Some(id.to_string()),
+            description: None,
+            depends_on: depends_on.map(|ids| ids.iter().map(|id| id.to_string()).collect()),
+            preconditions: vec![],
+            action: Action::Local {
+                repository: repository.to_path_buf(),
+                inner: LocalAction::CreateTags {
+                    new_tags: vec![id.to_string()],
+                    message: None,
+                },
+            },
+            postconditions: vec![],
+            continue_on_error: false,
+        }
+    }
+
+    /// Repository checked out on branch `topic`, with a crate and its
+    /// changelog.
+    fn fixture(path: &Path) {
+        repository(
+            path,
+            "topic",
+            &[
+                (
+                    "Cargo.toml",
+                    "[package]\nname = \"vm-fixture\"\nversion = \"0.1.0\" # \
+                     bumped\n\n[dependencies]\nvm-dep = { path = \"../vm-dep\", version = \
+                     \"0.1.0\" }\n",
+                ),
+                (
+                    "CHANGELOG.md",
+                    "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Everything\n",
+                ),
+            ],
+        );
+    }
+
+    /// Everything a plan may change in `repository`, without commit and tag
+    /// ids and dates.
+    fn repository_state(repository: &Path) -> String {
+        let mut state = git(repository, &["symbolic-ref", "HEAD"]);
+        state += &git(
+            repository,
+            &[
+                "for-each-ref",
+                "--format=%(refname) %(objecttype) %(*objecttype) %(tree)%(*tree)%0a%(contents)",
+            ],
+        );
+        state += &git(repository, &["log", "--all", "--format=%T%n%B"]);
+        state += &git(
+            repository,
+            &["status", "--porcelain", "--untracked-files=all"],
+        );
+        state
+    }
+
+    #[tokio::test]
+    async fn test_script_matches_execution() {
+        let dir = tempfile::tempdir().unwrap();
+        // `gh` reports `master` as the default branch.
+        let gh_bin = dir.path().join("gh");
+        write_executable(&gh_bin, "#!/bin/sh\necho master\n");
+        let mut context = crate::Context::new();
+        context.set_gh_bin(Some(gh_bin));
+        // Scripts always run `gh`.
+        context.set_github_backend(crate::github::Backend::Gh);
+
+        let plan = |repository: &Path| {
+            let item = |inner, continue_on_error| PlanItem {
+                id: None,
+                description: Some("It's\nan item".to_string()),
+                depends_on: None,
+                preconditions: vec![],
+                action: Action::Local {
+                    repository: repository.to_path_buf(),
+                    inner,
+                },
+                postconditions: vec![],
+                continue_on_error,
+            };
+            let tags = |tags: &[&str], message: Option<&str>| LocalAction::CreateTags {
+                new_tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                message: message.map(str::to_string),
+            };
+            Plan::new(vec![
+                // Fails, `topic` is checked out.
+                item(LocalAction::AssertDefaultBranch, true),
+                item(
+                    tags(
+                        &["x$(id)", "v0.2.0"],
+                        Some("# v0.2.0\n\n- `it's` $HOME \"quoted\"\n\\n %s\n"),
+                    ),
+                    false,
+                ),
+                item(
+                    LocalAction::CreateBranch {
+                        branch: "master".into(),
+                        base: "topic".into(),
+                    },
+                    false,
+                ),
+                item(LocalAction::AssertDefaultBranch, false),
+                // Both edit `Cargo.toml`, the script must not undo the first edit.
+                item(
+                    LocalAction::CommitDependencyUpdate {
+                        crate_name: "vm-fixture".into(),
+                        dependency_name: "vm-dep".into(),
+                        new_version: "0.2.0".into(),
+                        manifest: "Cargo.toml".into(),
+                    },
+                    false,
+                ),
+                item(
+                    LocalAction::CommitVersionBump {
+                        crate_name: "vm-fixture".into(),
+                        new_version: "0.2.0".into(),
+                        manifest: "Cargo.toml".into(),
+                        workspace_version: false,
+                        changelog: Some("CHANGELOG.md".into()),
+                        date: "2025-01-01".into(),
+                    },
+                    false,
+                ),
+                // Fails, the tag already exists.
+                item(tags(&["v0.2.0"], None), true),
+                item(tags(&["after-failure"], None), false),
+            ])
+        };
+
+        let native = dir.path().join("native repo's");
+        fixture(&native);
+        let mut native_plan = plan(&native);
+        let outcomes = execute(&mut native_plan, None, None, 1, &mut context)
+            .await
+            .unwrap();
+        let failed = outcomes
+            .iter()
+            .map(|outcome| matches!(outcome, ItemOutcome::Failed { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failed,
+            [true, false, false, false, false, false, true, false]
+        );
+
+        let script = dir.path().join("script $(id).sh");
+        let scripted = dir.path().join("scripted repo's");
+        fixture(&scripted);
+        let scripted_plan = plan(&scripted);
+        let graph = PlanGraph::new(&scripted_plan).unwrap();
+        std::fs::write(
+            &script,
+            to_shell_script(&scripted_plan, &graph, &mut context).unwrap(),
+        )
+        .unwrap();
+        let output = std::process::Command::new("sh")
+            .arg(&script)
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+
+        assert_eq!(repository_state(&native), repository_state(&scripted));
+        assert!(git(&native, &["tag", "--list"]).contains("x$(id)"));
+        let manifest = std::fs::read_to_string(scripted.join("Cargo.toml")).unwrap();
+        assert!(
+            manifest.contains("version = \"0.2.0\" # bumped"),
+            "{manifest}"
+        );
+        assert!(manifest.contains("version = \"0.2.0\" }"), "{manifest}");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let variables = Variables::from([
+            ("version".to_string(), serde_json::json!("1.2.0")),
+            ("jobs".to_string(), serde_json::json!(4)),
+            ("dry-run".to_string(), serde_json::json!(true)),
+            ("tags".to_string(), serde_json::json!(["v1.2.0", "v1"])),
+        ]);
+        let mut value = serde_json::json!({
+            "tags": "${tags}",
+            "jobs": "${jobs}",
+            "message": "Release v${version} with ${jobs} jobs, dry run: ${dry-run}",
+            "literal": ["$${version}", "$$", "${}", "${not a name}", "$"],
+            "missing": ["${unknown}", "v${other}"],
+        });
+        let mut unresolved = vec![];
+        substitute(&mut value, &variables, "items", &mut unresolved).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "tags": ["v1.2.0", "v1"],
+                "jobs": 4,
+                "message": "Release v1.2.0 with 4 jobs, dry run: true",
+                "literal": ["${version}", "$$", "${}", "${not a name}", "$"],
+                "missing": ["${unknown}", "v"],
+            })
+        );
+        assert_eq!(
+            unresolved,
+            [
+                "`unknown` at `items.missing[0]`",
+                "`other` at `items.missing[1]`"
+            ]
+        );
+
+        let mut value = serde_json::json!([{ "message": "Tags: ${tags}" }]);
+        let err = substitute(&mut value, &variables, "items", &mut vec![])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Plan variable `tags` at `items[0].message` is not a string, number or boolean and \
+             can not be part of a string."
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vars.toml");
+        std::fs::write(
+            &path,
+            "version = \"1.0.0\"\njobs = 4\ncrates = [\"a\", \"b\"]\n",
+        )
+        .unwrap();
+        let collected = variables(
+            Some(&path),
+            &[
+                "version=1.1.0".to_string(),
+                "dry-run=true".to_string(),
+                "empty=".to_string(),
+                "expr=a=b".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            collected,
+            Variables::from([
+                ("version".to_string(), serde_json::json!("1.1.0")),
+                ("jobs".to_string(), serde_json::json!(4)),
+                ("crates".to_string(), serde_json::json!(["a", "b"])),
+                ("dry-run".to_string(), serde_json::json!(true)),
+                ("empty".to_string(), serde_json::json!("")),
+                ("expr".to_string(), serde_json::json!("a=b")),
+            ])
+        );
+
+        let err = variables(None, &["version".to_string()])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Expected `KEY=VALUE`, got `version`.");
+        let err = variables(None, &["a b=c".to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Invalid plan variable name `a b`"), "{err}");
+    }
+
+    #[test]
+    fn test_graph_errors() {
+        let graph_error = |items| PlanGraph::new(&Plan::new(items)).unwrap_err().to_string();
+        let here = Path::new(".");
+
+        let err = graph_error(vec![
+            tag_item("a", None, here),
+            tag_item("b", None, here),
+            tag_item("a", None, here),
+        ]);
+        assert_eq!(
+            err,
+            "Plan items #0 and #2 have the same id `a`, ids must be unique."
+        );
+
+        let err = graph_error(vec![
+            tag_item("a", None, here),
+            tag_item("b", Some(&["a", "missing"]), here),
+        ]);
+        assert_eq!(
+            err,
+            "Plan item `b` depends on `missing` but no item has that id."
+        );
+
+        let err = graph_error(vec![tag_item("a", Some(&["a"]), here)]);
+        assert_eq!(err, "Plan item `a` depends on itself.");
+
+        let err = graph_error(vec![
+            tag_item("a", Some(&[]), here),
+            tag_item("b", Some(&["d"]), here),
+            tag_item("c", Some(&["b"]), here),
+            tag_item("d", Some(&["c"]), here),
+        ]);
+        assert_eq!(
+            err,
+            "Plan has a dependency cycle involving items `b`, `c`, `d`."
+        );
+    }
+
+    #[test]
+    fn test_graph_order() {
+        let here = Path::new(".");
+
+        // Without explicit dependencies, every item depends on the ones before it.
+        let graph = PlanGraph::new(&Plan::new(vec![
+            tag_item("a", None, here),
+            tag_item("b", None, here),
+            tag_item("c", None, here),
+        ]))
+        .unwrap();
+        assert_eq!(graph.dependencies, [vec![], vec![0], vec![0, 1]]);
+        assert_eq!(graph.order, [0, 1, 2]);
+
+        // Items that are ready at the same time run in plan order.
+        let graph = PlanGraph::new(&Plan::new(vec![
+            tag_item("a", Some(&[]), here),
+            tag_item("b", Some(&["c"]), here),
+            tag_item("c", Some(&[]), here),
+            tag_item("d", Some(&["a"]), here),
+            tag_item("e", Some(&["d", "b", "d"]), here),
+        ]))
+        .unwrap();
+        assert_eq!(
+            graph.dependencies,
+            [vec![], vec![2], vec![], vec![0], vec![1, 3]]
+        );
+        assert_eq!(graph.order, [0, 2, 1, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_failure_skips_dependents() {
+        let dir = tempfile::tempdir().unwrap();
+        let repository = dir.path().join("repository");
+        fixture(&repository);
+        let mut context = crate::Context::new();
+
+        let plan = |continue_on_error| {
+            let mut failing = tag_item("taken", Some(&[]), &repository);
+            failing.continue_on_error = continue_on_error;
+            Plan::new(vec![
+                failing,
+                tag_item("after", Some(&["taken"]), &repository),
+                tag_item("transitive", Some(&["after"]), &repository),
+                tag_item("unrelated", Some(&[]), &repository),
+            ])
+        };
+
+        // Fails, the tag already exists.
+        git(&repository, &["tag", "taken"]);
+        let outcomes = execute(&mut plan(false), None, None, 1, &mut context)
+            .await
+            .unwrap();
+        assert!(matches!(outcomes[0], ItemOutcome::Failed { .. }));
+        assert_eq!(
+            outcomes[1..],
+            [
+                ItemOutcome::Skipped { dependency: 0 },
+                ItemOutcome::Skipped { dependency: 1 },
+                ItemOutcome::Succeeded,
+            ]
+        );
+        assert_eq!(git(&repository, &["tag", "--list"]), "taken\nunrelated\n");
+
+        git(&repository, &["tag", "--delete", "unrelated"]);
+        let outcomes = execute(&mut plan(true), None, None, 1, &mut context)
+            .await
+            .unwrap();
+        assert!(matches!(outcomes[0], ItemOutcome::Failed { .. }));
+        assert_eq!(
+            outcomes[1..],
+            [
+                ItemOutcome::Succeeded,
+                ItemOutcome::Succeeded,
+                ItemOutcome::Succeeded,
+            ]
+        );
+        assert_eq!(
+            git(&repository, &["tag", "--list"]),
+            "after\ntaken\ntransitive\nunrelated\n"
+        );
+    }
+
+    #[test]
+    fn test_journal_edited_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.json.journal");
+        let plan = Plan::new(vec![create_tag("v1")]);
+        let edited = create_tag("v1-edited");
+
+        let mut journal = Journal::open(&path, false).unwrap();
+        journal
+            .record(
+                &plan,
+                0,
+                JournalEvent::Edited {
+                    item: Box::new(edited.clone()),
+                },
+            )
+            .unwrap();
+        let mut executed = plan.clone();
+        executed.items[0] = edited.clone();
+        journal.record(&executed, 0, JournalEvent::Started).unwrap();
+        drop(journal);
+
+        // The journal still belongs to the plan on disk, and resuming executes the
+        // edited item.
+        let journal = Journal::open(&path, true).unwrap();
+        assert_eq!(
+            journal.last_events(&plan).unwrap(),
+            HashMap::from([(0, JournalEvent::Started)])
+        );
+        let mut resumed = plan.clone();
+        journal.apply_edits(&mut resumed);
+        assert_eq!(resumed, executed);
+
+        // A plan modified by other means is still rejected.
+        let err = journal.last_events(&executed).unwrap_err().to_string();
+        assert!(err.contains("was the plan modified?"), "{err}");
+    }
+}
@@ -0,0 +1,312 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! External commands that make up an action.
+//!
+//! Actions describe themselves as a list of [`Step`]s, which are either
+//! executed directly or rendered as shell script, so that both ways of running
+//! a plan do exactly the same thing.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{changelog, manifest, utilities::shell};
+
+/// Here-document delimiter for file contents in shell scripts.
+const HEREDOC_DELIMITER: &str = "RUST_VMM_HELPER_CLI_EOF";
+
+/// Contents of files as left by the [`Step::EditFile`] steps rendered so far,
+/// keyed by their path including the repository.
+///
+/// Rendering a shell script does not change the files on disk, so later edits
+/// of the same file must be applied on top of the earlier ones.
+pub type EditedFiles = HashMap<PathBuf, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Run command, writing `stdin` to its standard input if given.
+    Run {
+        argv: Vec<String>,
+        stdin: Option<String>,
+    },
+    /// Run both commands and fail with `message` unless their outputs are
+    /// equal, ignoring trailing whitespace.
+    AssertSameOutput {
+        left: Vec<String>,
+        right: Vec<String>,
+        message: String,
+    },
+    /// Run `list`, then run `command` once for every non-empty line of its
+    /// output with the line appended as last argument.
+    ForEachLine {
+        list: Vec<String>,
+        command: Vec<String>,
+    },
+    /// Run command until it succeeds, failing with `message` after `timeout`
+    /// seconds. The delay between attempts starts at `interval` seconds and
+    /// doubles up to `max_interval`.
+    Poll {
+        argv: Vec<String>,
+        interval: u64,
+        max_interval: u64,
+        timeout: u64,
+        message: String,
+    },
+    /// Replace file at `path`, relative to the repository, with the result of
+    /// `edit`.
+    EditFile { path: PathBuf, edit: FileEdit },
+}
+
+/// Change of a file's contents, see [`Step::EditFile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileEdit {
+    /// Set `package.version` of a `Cargo.toml`.
+    SetPackageVersion { version: String },
+    /// Set `workspace.package.version` of a workspace root `Cargo.toml`.
+    SetWorkspacePackageVersion { version: String },
+    /// Turn the unreleased section of a `CHANGELOG.md` into that of
+    /// `version`.
+    ReleaseChangelog { version: String, date: String },
+    /// Set the version requirement of path dependency `dependency` in a
+    /// `Cargo.toml`.
+    SetDependencyVersion { dependency: String, version: String },
+}
+
+impl FileEdit {
+    pub fn apply(&self, contents: &str) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            Self::SetPackageVersion { version } => manifest::set_package_version(contents, version),
+            Self::SetWorkspacePackageVersion { version } => {
+                manifest::set_workspace_package_version(contents, version)
+            }
+            Self::ReleaseChangelog { version, date } => changelog::release(contents, version, date),
+            Self::SetDependencyVersion {
+                dependency,
+                version,
+            } => manifest::set_dependency_version(contents, dependency, version),
+        }
+    }
+}
+
+impl Step {
+    /// Shorthand for a [`Step::Run`] without standard input.
+    pub fn run<S: Into<String>>(argv: impl IntoIterator<Item = S>) -> Self {
+        Self::Run {
+            argv: argv.into_iter().map(Into::into).collect(),
+            stdin: None,
+        }
+    }
+
+    /// Run step with `repository` as working directory.
+    pub async fn execute(&self, repository: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Run { argv, stdin } => {
+                output(argv, stdin.as_deref(), repository).await?;
+            }
+            Self::AssertSameOutput {
+                left,
+                right,
+                message,
+            } => {
+                let left = output(left, None, repository).await?;
+                let right = output(right, None, repository).await?;
+                if left.trim_end() != right.trim_end() {
+                    return Err(format!(
+                        "{message} (`{}` != `{}`)",
+                        left.trim_end(),
+                        right.trim_end()
+                    )
+                    .into());
+                }
+            }
+            Self::ForEachLine { list, command } => {
+                let lines = output(list, None, repository).await?;
+                for line in lines.lines() {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let mut argv = command.clone();
+                    argv.push(line.to_string());
+                    output(&argv, None, repository).await?;
+                }
+            }
+            Self::Poll {
+                argv,
+                interval,
+                max_interval,
+                timeout,
+                message,
+            } => {
+                let start = Instant::now();
+                let timeout = Duration::from_secs(*timeout);
+                let mut interval = *interval;
+                loop {
+                    let err = match output(argv, None, repository).await {
+                        Ok(_) => break,
+                        Err(err) => err.to_string(),
+                    };
+                    let elapsed = start.elapsed();
+                    if elapsed >= timeout {
+                        return Err(format!("{message} ({err})").into());
+                    }
+                    tokio::time::sleep(Duration::from_secs(interval).min(timeout - elapsed)).await;
+                    interval = (interval * 2).min(*max_interval);
+                }
+            }
+            Self::EditFile { path, edit } => {
+                let path = repository.join(path);
+                log::info!("Editing `{}`", path.display());
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Could not read `{}`: {err}", path.display()))?;
+                let edited = edit
+                    .apply(&contents)
+                    .map_err(|err| format!("Could not edit `{}`: {err}", path.display()))?;
+                std::fs::write(&path, edited)
+                    .map_err(|err| format!("Could not write `{}`: {err}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Render as shell script lines. Failures exit the script, unless
+    /// `continue_on_error` is set.
+    ///
+    /// File edits are applied to the contents of the file in `repository` as
+    /// left by earlier steps in `edited_files`, or on disk, and written out
+    /// verbatim, so returns `None` if the file can not be read or edited.
+    pub fn as_shell_command(
+        &self,
+        repository: &Path,
+        continue_on_error: bool,
+        edited_files: &mut EditedFiles,
+    ) -> Option<Vec<String>> {
+        let on_error = shell::on_error(continue_on_error);
+        Some(match self {
+            Self::Run { argv, stdin: None } => vec![format!("{}{on_error}", shell::join(argv))],
+            Self::Run {
+                argv,
+                stdin: Some(stdin),
+            } => vec![format!(
+                "printf '%s' {} | {}{on_error}",
+                shell::quote(stdin),
+                shell::join(argv)
+            )],
+            Self::AssertSameOutput {
+                left,
+                right,
+                message,
+            } => vec![format!(
+                "[ \"$({})\" = \"$({})\" ] || {{ echo {} >&2{}; }}",
+                shell::join(left),
+                shell::join(right),
+                shell::quote(message),
+                if continue_on_error { "" } else { "; exit 1" }
+            )],
+            Self::ForEachLine { list, command } => vec![
+                format!("lines=$({}){on_error}", shell::join(list)),
+                format!(
+                    "for line in $lines; do {} \"$line\"{on_error}; done",
+                    shell::join(command)
+                ),
+            ],
+            Self::Poll {
+                argv,
+                interval,
+                max_interval,
+                timeout,
+                message,
+            } => vec![
+                format!("deadline=$(($(date +%s) + {timeout})); delay={interval}"),
+                format!(
+                    "until {} >/dev/null; do left=$((deadline - $(date +%s))); [ \"$left\" -gt 0 \
+                     ] || {{ echo {} >&2; {}; }}; sleep $((delay < left ? delay : left)); \
+                     delay=$((delay * 2)); [ \"$delay\" -le {max_interval} ] || \
+                     delay={max_interval}; done",
+                    shell::join(argv),
+                    shell::quote(message),
+                    if continue_on_error { "break" } else { "exit 1" }
+                ),
+            ],
+            Self::EditFile { path, edit } => {
+                let full_path = repository.join(path);
+                let contents = match edited_files.get(&full_path) {
+                    Some(contents) => contents.clone(),
+                    None => std::fs::read_to_string(&full_path).ok()?,
+                };
+                let edited = edit.apply(&contents).ok()?;
+                if edited.lines().any(|line| line == HEREDOC_DELIMITER) {
+                    return None;
+                }
+                edited_files.insert(full_path, edited.clone());
+                vec![format!(
+                    "cat > {} <<'{HEREDOC_DELIMITER}'{on_error}\n{}{}{HEREDOC_DELIMITER}",
+                    shell::quote(path.to_str()?),
+                    edited,
+                    if edited.ends_with('\n') { "" } else { "\n" }
+                )]
+            }
+        })
+    }
+}
+
+/// Run command in `dir` and return its standard output, or an error if it
+/// fails.
+async fn output(
+    argv: &[String],
+    stdin: Option<&str>,
+    dir: &Path,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let Some((program, args)) = argv.split_first() else {
+        return Err("Empty command".into());
+    };
+    log::info!("Running `{}`", shell::join(argv));
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Could not run `{}`: {err}", shell::join(argv)))?;
+    if let Some(input) = stdin {
+        let mut child_stdin = child.stdin.take().unwrap();
+        child_stdin.write_all(input.as_bytes()).await?;
+        // Close standard input so that the command sees EOF.
+        drop(child_stdin);
+    }
+    let output = child.wait_with_output().await?;
+
+    if !output.status.success() {
+        return Err(format!("`{}` failed: {output:?}", shell::join(argv)).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -37,6 +37,47 @@ pub mod codeowners {
     }
 }
 
+pub mod shell {
+    use std::borrow::Cow;
+
+    /// Quote `arg` so that a POSIX shell reads it as a single word, unless it
+    /// consists only of characters that need no quoting.
+    pub fn quote(arg: &str) -> Cow<'_, str> {
+        if !arg.is_empty()
+            && arg.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || matches!(c, '_' | '-' | '.' | '/' | ':' | ',' | '+' | '=' | '@' | '%')
+            })
+        {
+            return Cow::Borrowed(arg);
+        }
+        Cow::Owned(format!("'{}'", arg.replace('\'', r#"'\''"#)))
+    }
+
+    /// Join `argv` into a shell command line, quoting every argument.
+    pub fn join<S: AsRef<str>>(argv: &[S]) -> String {
+        argv.iter()
+            .map(|arg| quote(arg.as_ref()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `cd` into `path`, exiting the script on failure. Returns `None` if the
+    /// path is not valid UTF-8.
+    pub fn cd(path: &std::path::Path) -> Option<String> {
+        Some(format!("cd {} || exit 1", quote(path.to_str()?)))
+    }
+
+    /// Suffix for commands whose failure should (not) exit the script.
+    pub fn on_error(continue_on_error: bool) -> &'static str {
+        if continue_on_error {
+            " || true"
+        } else {
+            " || exit 1"
+        }
+    }
+}
+
 pub mod repository {
     use std::path::PathBuf;
 
@@ -44,55 +85,275 @@ pub mod repository {
     use serde_derive::{Deserialize, Serialize};
 
     #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-    #[serde(rename_all = "camelCase")]
+    #[serde(rename_all = "kebab-case")]
     pub struct Manifest {
         pub package: Package,
         #[serde(default)]
         pub dependencies: IndexMap<String, DependencyField>,
+        #[serde(default, alias = "dev_dependencies")]
+        pub dev_dependencies: IndexMap<String, DependencyField>,
+        #[serde(default, alias = "build_dependencies")]
+        pub build_dependencies: IndexMap<String, DependencyField>,
+        /// Platform specific dependencies, keyed by `cfg(..)` expression or
+        /// target triple.
+        #[serde(default)]
+        pub target: IndexMap<String, TargetDependencies>,
+        #[serde(default)]
+        pub features: IndexMap<String, Vec<String>>,
+    }
+
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct TargetDependencies {
+        #[serde(default)]
+        pub dependencies: IndexMap<String, DependencyField>,
+        #[serde(default, alias = "dev_dependencies")]
+        pub dev_dependencies: IndexMap<String, DependencyField>,
+        #[serde(default, alias = "build_dependencies")]
+        pub build_dependencies: IndexMap<String, DependencyField>,
     }
 
     #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Package {
         pub name: String,
-        pub version: String,
+        pub version: Inheritable<String>,
         #[serde(default)]
-        pub edition: Option<String>,
+        pub edition: Option<Inheritable<String>>,
         #[serde(default)]
-        pub authors: Vec<String>,
+        pub authors: Option<Inheritable<Vec<String>>>,
         #[serde(default)]
-        pub description: Option<String>,
+        pub description: Option<Inheritable<String>>,
         #[serde(default)]
-        pub repository: Option<String>,
+        pub repository: Option<Inheritable<String>>,
         #[serde(default)]
-        pub readme: Option<String>,
+        pub readme: Option<Inheritable<String>>,
         #[serde(default)]
-        pub license: Option<String>,
+        pub license: Option<Inheritable<String>>,
         #[serde(default)]
-        pub publish: bool,
+        pub publish: Option<Inheritable<Publish>>,
+    }
+
+    impl Package {
+        /// Effective values of the inheritable fields, taking inherited ones
+        /// from `workspace`.
+        pub fn resolve(
+            &self,
+            workspace: Option<&WorkspacePackage>,
+        ) -> Result<WorkspacePackage, Box<dyn std::error::Error>> {
+            fn field<T: Clone>(
+                name: &str,
+                value: &Option<Inheritable<T>>,
+                workspace: &Option<T>,
+            ) -> Result<Option<T>, Box<dyn std::error::Error>> {
+                value
+                    .as_ref()
+                    .map(|v| v.resolve(name, workspace.as_ref()))
+                    .transpose()
+            }
+
+            let default = WorkspacePackage::default();
+            let workspace = workspace.unwrap_or(&default);
+            Ok(WorkspacePackage {
+                version: Some(
+                    self.version
+                        .resolve("version", workspace.version.as_ref())?,
+                ),
+                edition: field("edition", &self.edition, &workspace.edition)?,
+                authors: field("authors", &self.authors, &workspace.authors)?,
+                description: field("description", &self.description, &workspace.description)?,
+                repository: field("repository", &self.repository, &workspace.repository)?,
+                readme: field("readme", &self.readme, &workspace.readme)?,
+                license: field("license", &self.license, &workspace.license)?,
+                publish: field("publish", &self.publish, &workspace.publish)?,
+            })
+        }
+    }
+
+    /// `package.publish`: whether the package may be published at all, or
+    /// the registries it may be published to. Packages are publishable
+    /// everywhere unless stated otherwise.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Publish {
+        Allowed(bool),
+        Registries(Vec<String>),
+    }
+
+    impl Default for Publish {
+        fn default() -> Self {
+            Self::Allowed(true)
+        }
+    }
+
+    impl Publish {
+        /// Registries the package may be published to, like `cargo metadata`
+        /// reports them: `None` means any, an empty list means none.
+        pub fn registries(&self) -> Option<Vec<String>> {
+            match self {
+                Self::Allowed(true) => None,
+                Self::Allowed(false) => Some(vec![]),
+                Self::Registries(registries) => Some(registries.clone()),
+            }
+        }
+    }
+
+    /// A package field that is either set in the package manifest or
+    /// inherited from `[workspace.package]` with `field.workspace = true`.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Inheritable<T> {
+        Value(T),
+        Inherited { workspace: bool },
+    }
+
+    impl<T: Default> Default for Inheritable<T> {
+        fn default() -> Self {
+            Self::Value(T::default())
+        }
+    }
+
+    impl<T: Clone> Inheritable<T> {
+        /// Effective value of `field`, given its value in
+        /// `[workspace.package]`.
+        pub fn resolve(
+            &self,
+            field: &str,
+            workspace: Option<&T>,
+        ) -> Result<T, Box<dyn std::error::Error>> {
+            match self {
+                Self::Value(value) => Ok(value.clone()),
+                Self::Inherited { workspace: true } => workspace.cloned().ok_or_else(|| {
+                    format!("`{field}` is inherited but not set in [workspace.package]").into()
+                }),
+                Self::Inherited { workspace: false } => {
+                    Err(format!("`{field}.workspace` cannot be false").into())
+                }
+            }
+        }
     }
 
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(untagged)]
     pub enum DependencyField {
         Version(String),
+        /// `dep = { workspace = true }`, refers to `[workspace.dependencies]`.
+        Inherited(InheritedDependency),
         Dependency(Dependency),
     }
 
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Dependency {
+        /// Version requirement, which path and git dependencies may omit.
+        #[serde(default)]
+        pub version: Option<String>,
+        #[serde(default)]
+        pub path: Option<PathBuf>,
+        #[serde(default)]
+        pub git: Option<String>,
+        #[serde(default)]
+        pub branch: Option<String>,
+        #[serde(default)]
+        pub tag: Option<String>,
+        #[serde(default)]
+        pub rev: Option<String>,
+        #[serde(default)]
+        pub registry: Option<String>,
+        /// Name of the depended-on package, if it differs from the key the
+        /// dependency is declared under.
+        #[serde(default)]
+        pub package: Option<String>,
+        #[serde(default)]
+        pub optional: bool,
+        #[serde(default = "default_true", alias = "default_features")]
+        pub default_features: bool,
+        #[serde(default)]
+        pub features: Vec<String>,
+    }
+
+    const fn default_true() -> bool {
+        true
+    }
+
+    impl Dependency {
+        /// Source of a non-path dependency, in the format `cargo metadata`
+        /// uses. Dependencies on registries other than crates.io have none.
+        pub fn source(&self) -> Option<String> {
+            if let Some(git) = &self.git {
+                let reference = [
+                    ("branch", &self.branch),
+                    ("tag", &self.tag),
+                    ("rev", &self.rev),
+                ]
+                .into_iter()
+                .find_map(|(kind, value)| Some(format!("?{kind}={}", value.as_ref()?)))
+                .unwrap_or_default();
+                return Some(format!("git+{git}{reference}"));
+            }
+            (self.path.is_none() && self.registry.is_none())
+                .then(|| "registry+https://github.com/rust-lang/crates.io-index".to_string())
+        }
+    }
+
+    /// A dependency inherited from `[workspace.dependencies]`. Only
+    /// `features` (which are added to the workspace ones) and `optional` may
+    /// be set next to `workspace = true`.
     #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
-    pub struct Dependency {
-        pub version: String,
+    pub struct InheritedDependency {
+        pub workspace: bool,
         #[serde(default)]
         pub features: Vec<String>,
         #[serde(default)]
-        pub path: Option<PathBuf>,
+        pub optional: bool,
     }
 
     #[derive(Debug, Clone, PartialEq)]
     pub struct Crate {
         pub manifest_path: PathBuf,
-        pub manifest: Manifest,
+        pub package: crate::workspace::Package,
+    }
+
+    impl Crate {
+        /// Names of the crate's dependencies that are in `crates`.
+        /// Dev-dependencies are not counted, since they don't need to be
+        /// published first.
+        pub fn dependencies_among<'c>(&self, crates: &'c [Crate]) -> Vec<&'c str> {
+            crates
+                .iter()
+                .map(|c| c.package.name.as_str())
+                .filter(|name| {
+                    *name != self.package.name
+                        && self.package.non_dev_dependencies().any(|d| d.name == *name)
+                })
+                .collect()
+        }
+    }
+
+    /// Sort `crates` so that every crate comes after its dependencies among
+    /// them, keeping their order otherwise. Fails if dependencies are cyclic.
+    pub fn sort_by_dependencies(
+        mut crates: Vec<Crate>,
+    ) -> Result<Vec<Crate>, Box<dyn std::error::Error>> {
+        let mut sorted = Vec::with_capacity(crates.len());
+        while !crates.is_empty() {
+            let Some(next) = crates
+                .iter()
+                .position(|c| c.dependencies_among(&crates).is_empty())
+            else {
+                return Err(format!(
+                    "Dependencies between {:?} are cyclic",
+                    crates
+                        .iter()
+                        .map(|c| c.package.name.as_str())
+                        .collect::<Vec<_>>()
+                )
+                .into());
+            };
+            sorted.push(crates.remove(next));
+        }
+        Ok(sorted)
     }
 
     #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -109,54 +370,159 @@ pub mod repository {
         pub members: Vec<String>,
         #[serde(default)]
         pub exclude: Vec<String>,
+        #[serde(default)]
+        pub package: WorkspacePackage,
+        #[serde(default)]
+        pub dependencies: IndexMap<String, DependencyField>,
+    }
+
+    /// Values members can inherit from `[workspace.package]`.
+    #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct WorkspacePackage {
+        #[serde(default)]
+        pub version: Option<String>,
+        #[serde(default)]
+        pub edition: Option<String>,
+        #[serde(default)]
+        pub authors: Option<Vec<String>>,
+        #[serde(default)]
+        pub description: Option<String>,
+        #[serde(default)]
+        pub repository: Option<String>,
+        #[serde(default)]
+        pub readme: Option<String>,
+        #[serde(default)]
+        pub license: Option<String>,
+        #[serde(default)]
+        pub publish: Option<Publish>,
     }
 
+    /// Find package `package_name` in the workspace (or single package)
+    /// at `path`, also looking in directories excluded from the workspace.
     pub fn from_dir(
         package_name: &str,
         path: &std::path::Path,
+        context: &crate::Context,
     ) -> Result<Crate, Box<dyn std::error::Error>> {
-        let path = path.canonicalize()?;
-        if let Ok(workspace_manifest) =
-            toml::from_str::<WorkspaceManifest>(&std::fs::read_to_string(path.join("Cargo.toml"))?)
-        {
-            for member in workspace_manifest
-                .workspace
-                .members
-                .iter()
-                .chain(workspace_manifest.workspace.exclude.iter())
+        let workspace = crate::workspace::Workspace::load(path, context)?;
+        from_workspace(package_name, &workspace, context)
+    }
+
+    /// Find package `package_name` among the members of `workspace`, also
+    /// looking in directories excluded from it.
+    pub fn from_workspace(
+        package_name: &str,
+        workspace: &crate::workspace::Workspace,
+        context: &crate::Context,
+    ) -> Result<Crate, Box<dyn std::error::Error>> {
+        if let Some(package) = workspace.package(package_name) {
+            log::trace!("found {package_name:?} at path {:?}", package.manifest_path);
+            return Ok(Crate {
+                manifest_path: package.manifest_path.clone(),
+                package: package.clone(),
+            });
+        }
+        let exclude = std::fs::read_to_string(workspace.root.join("Cargo.toml"))
+            .ok()
+            .and_then(|m| toml::from_str::<WorkspaceManifest>(&m).ok())
+            .map(|m| m.workspace.exclude)
+            .unwrap_or_default();
+        for excluded in &exclude {
+            let dir = workspace.root.join(excluded);
+            log::trace!("for excluded = {excluded:?} checking path {dir:?}");
+            if !dir.join("Cargo.toml").is_file() {
+                continue;
+            }
+            if let Some(package) =
+                crate::workspace::Workspace::load(&dir, context)?.package(package_name)
             {
-                let manifest_path = path.join(member).join("Cargo.toml");
-                log::trace!("for member = {member:?} checking path {manifest_path:?}",);
-                if let Ok(manifest) = std::fs::read_to_string(&manifest_path)
-                    .map_err(<Box<dyn std::error::Error>>::from)
-                    .and_then(|m| toml::from_str::<Manifest>(&m).map_err(|err| err.into()))
-                {
-                    log::trace!(
-                        "found {:?} at path {:?}",
-                        manifest.package.name,
-                        manifest_path
-                    );
-                    if manifest.package.name == package_name {
-                        return Ok(Crate {
-                            manifest_path,
-                            manifest,
-                        });
-                    }
-                }
+                return Ok(Crate {
+                    manifest_path: package.manifest_path.clone(),
+                    package: package.clone(),
+                });
             }
-            Err(format!(
-                "Could not find package {package_name}, found a workspace with members = {:?} and \
-                 exclude = {:?}",
-                workspace_manifest.workspace.members, workspace_manifest.workspace.exclude,
-            )
-            .into())
-        } else {
-            let manifest_path = path.join("Cargo.toml");
-            let manifest = toml::from_str::<Manifest>(&std::fs::read_to_string(&manifest_path)?)?;
-            Ok(Crate {
+        }
+        Err(format!(
+            "Could not find package {package_name}, found a workspace with members = {:?} and \
+             exclude = {exclude:?}",
+            workspace.member_names(),
+        )
+        .into())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Crate `name` with a normal dependency on each of `dependencies`
+        /// and a dev-dependency on each of `dev_dependencies`.
+        fn krate(name: &str, dependencies: &[&str], dev_dependencies: &[&str]) -> Crate {
+            let dependencies = dependencies
+                .iter()
+                .map(|d| serde_json::json!({ "name": d, "req": "*" }))
+                .chain(
+                    dev_dependencies
+                        .iter()
+                        .map(|d| serde_json::json!({ "name": d, "req": "*", "kind": "dev" })),
+                )
+                .collect::<Vec<_>>();
+            let manifest_path = PathBuf::from(name).join("Cargo.toml");
+            Crate {
+                package: serde_json::from_value(serde_json::json!({
+                    "name": name,
+                    "version": "0.1.0",
+                    "manifest_path": manifest_path,
+                    "dependencies": dependencies,
+                }))
+                .unwrap(),
                 manifest_path,
-                manifest,
-            })
+            }
+        }
+
+        fn names(crates: &[Crate]) -> Vec<&str> {
+            crates.iter().map(|c| c.package.name.as_str()).collect()
+        }
+
+        #[test]
+        fn test_dependencies_among() {
+            let crates = [
+                krate("a", &["b", "serde", "a"], &["c"]),
+                krate("b", &[], &[]),
+                krate("c", &["b"], &[]),
+            ];
+            assert_eq!(crates[0].dependencies_among(&crates), ["b"]);
+            assert!(crates[1].dependencies_among(&crates).is_empty());
+            assert_eq!(crates[2].dependencies_among(&crates), ["b"]);
+        }
+
+        #[test]
+        fn test_sort_by_dependencies() {
+            let sorted = sort_by_dependencies(vec![
+                krate("a", &["c"], &[]),
+                krate("b", &[], &[]),
+                krate("c", &["d"], &[]),
+                krate("d", &[], &[]),
+                krate("e", &["b"], &[]),
+            ])
+            .unwrap();
+            assert_eq!(names(&sorted), ["b", "d", "c", "a", "e"]);
+
+            // Dev-dependencies do not need to be published first, so they neither
+            // reorder crates nor make them cyclic.
+            let sorted =
+                sort_by_dependencies(vec![krate("a", &[], &["b"]), krate("b", &["a"], &[])])
+                    .unwrap();
+            assert_eq!(names(&sorted), ["a", "b"]);
+
+            let err = sort_by_dependencies(vec![
+                krate("a", &[], &[]),
+                krate("b", &["c"], &[]),
+                krate("c", &["b"], &[]),
+            ])
+            .unwrap_err()
+            .to_string();
+            assert_eq!(err, r#"Dependencies between ["b", "c"] are cyclic"#);
         }
     }
 }
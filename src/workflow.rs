@@ -0,0 +1,327 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Export of action plans as GitHub Actions workflows.
+//!
+//! The workflow has a single job with one step per plan item, in execution
+//! order. Each step runs the same shell commands as the `--shellscript-output`
+//! export, from the root of the checked out repository.
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, LocalAction, Plan},
+    plan::PlanGraph,
+    steps::EditedFiles,
+    utilities::shell,
+};
+
+/// Repository secret holding the crates.io API token.
+pub const CRATES_IO_TOKEN_SECRET: &str = "CARGO_REGISTRY_TOKEN";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workflow {
+    pub name: String,
+    pub on: IndexMap<String, serde_yaml::Value>,
+    pub permissions: IndexMap<String, String>,
+    pub jobs: IndexMap<String, Job>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Job {
+    pub runs_on: String,
+    pub defaults: serde_yaml::Value,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Step {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uses: Option<String>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub with: IndexMap<String, serde_yaml::Value>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub env: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continue_on_error: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+}
+
+/// Render `plan` as a workflow named `name`, with items in the order of
+/// `graph`.
+///
+/// Since the workflow runs in a checkout of a single repository, all plan
+/// items must refer to the same one.
+pub fn render(
+    plan: &Plan,
+    graph: &PlanGraph,
+    name: &str,
+    context: &mut crate::Context,
+) -> Result<Workflow, Box<dyn std::error::Error>> {
+    let mut repositories = plan
+        .items
+        .iter()
+        .map(|plan_item| plan_item.action.repository())
+        .collect::<Vec<_>>();
+    repositories.sort();
+    repositories.dedup();
+    if repositories.len() > 1 {
+        return Err(format!(
+            "Workflows can only be exported for plans on a single repository, found: {}",
+            repositories
+                .iter()
+                .map(|r| format!("`{}`", r.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into());
+    }
+
+    let mut steps = vec![
+        Step {
+            name: "Check out repository".into(),
+            uses: Some("actions/checkout@v4".into()),
+            // Tag conditions need the full history and tags.
+            with: IndexMap::from([("fetch-depth".into(), 0.into())]),
+            ..Step::default()
+        },
+        Step {
+            name: "Configure git".into(),
+            run: Some(
+                [
+                    "git config user.name 'github-actions[bot]'",
+                    "git config user.email '41898282+github-actions[bot]@users.noreply.github.com'",
+                    // Plans push to `upstream` by default, which is the checked out repository
+                    // here.
+                    "git remote get-url upstream || git remote add upstream \"$(git remote \
+                     get-url origin)\"",
+                ]
+                .join("\n"),
+            ),
+            ..Step::default()
+        },
+    ];
+    let mut edited_files = EditedFiles::new();
+    for &i in &graph.order {
+        let plan_item = &plan.items[i];
+        let Some(cmds) = plan_item.as_shell_command(i, &mut edited_files, context) else {
+            return Err(format!(
+                "Can not represent plan item {} as shell script",
+                plan_item.label(i)
+            )
+            .into());
+        };
+        let cd = shell::cd(plan_item.action.repository());
+        let run = cmds
+            .into_iter()
+            .filter(|cmd| Some(cmd) != cd.as_ref())
+            .collect::<Vec<_>>();
+        let mut env = IndexMap::new();
+        match plan_item.action {
+            Action::CratesIO { .. } => {
+                env.insert(
+                    "CARGO_REGISTRY_TOKEN".into(),
+                    format!("${{{{ secrets.{CRATES_IO_TOKEN_SECRET} }}}}"),
+                );
+            }
+            Action::Github { .. }
+            | Action::Local {
+                inner: LocalAction::AssertDefaultBranch,
+                ..
+            } => {
+                env.insert("GH_TOKEN".into(), "${{ github.token }}".into());
+            }
+            Action::Local { .. } => {}
+        }
+        steps.push(Step {
+            name: plan_item
+                .id
+                .clone()
+                .or_else(|| plan_item.description.clone())
+                .unwrap_or_else(|| format!("#{i}")),
+            env,
+            continue_on_error: plan_item.continue_on_error,
+            run: Some(if run.is_empty() {
+                "true".into()
+            } else {
+                run.join("\n")
+            }),
+            ..Step::default()
+        });
+    }
+
+    Ok(Workflow {
+        name: name.to_string(),
+        on: IndexMap::from([("workflow_dispatch".into(), serde_yaml::Value::Null)]),
+        permissions: IndexMap::from([
+            ("contents".into(), "write".into()),
+            ("pull-requests".into(), "write".into()),
+        ]),
+        jobs: IndexMap::from([(
+            "release".into(),
+            Job {
+                runs_on: "ubuntu-latest".into(),
+                defaults: serde_yaml::to_value(IndexMap::from([(
+                    "run",
+                    IndexMap::from([("shell", "sh -e {0}")]),
+                )]))?,
+                steps,
+            },
+        )]),
+    })
+}
+
+/// Serialize `workflow` to YAML.
+pub fn to_yaml(workflow: &Workflow) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(serde_yaml::to_string(workflow)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_yaml::Value;
+
+    use super::*;
+    use crate::actions::{CratesIOAction, GithubAction, PlanItem};
+
+    fn item(id: &str, action: Action, continue_on_error: bool) -> PlanItem {
+        PlanItem {
+            id: Some(id.to_string()),
+            description: None,
+            depends_on: None,
+            preconditions: vec![],
+            action,
+            postconditions: vec![],
+            continue_on_error,
+        }
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        let repository = PathBuf::from("/src/vm memory's");
+        let plan = Plan::new(vec![
+            item(
+                "tag",
+                Action::Local {
+                    repository: repository.clone(),
+                    inner: LocalAction::CreateTags {
+                        new_tags: vec!["x$(id)".into()],
+                        message: Some("# Release\n\n- `it's` $HOME\n".into()),
+                    },
+                },
+                true,
+            ),
+            item(
+                "publish",
+                Action::CratesIO {
+                    repository: repository.clone(),
+                    inner: CratesIOAction::Publish {
+                        crate_name: "vm-memory".into(),
+                        dry_run: false,
+                    },
+                },
+                false,
+            ),
+            item(
+                "release",
+                Action::Github {
+                    repository,
+                    inner: GithubAction::CreateRelease {
+                        crate_name: "vm-memory".into(),
+                        tag: "x$(id)".into(),
+                        version: "1.0.0".into(),
+                        notes: Some("multi\nline".into()),
+                    },
+                },
+                false,
+            ),
+        ]);
+        let graph = PlanGraph::new(&plan).unwrap();
+        let mut context = crate::Context::new();
+        let workflow = render(&plan, &graph, "release", &mut context).unwrap();
+        let yaml = to_yaml(&workflow).unwrap();
+        let value: Value = serde_yaml::from_str(&yaml).unwrap();
+
+        assert_eq!(value["name"], Value::from("release"));
+        // `on` must not be read back as the boolean `true`.
+        let on = value["on"].as_mapping().expect(&yaml);
+        assert!(on.contains_key("workflow_dispatch"), "{yaml}");
+        assert_eq!(value["permissions"]["contents"], Value::from("write"));
+
+        let steps = value["jobs"]["release"]["steps"].as_sequence().unwrap();
+        let names = steps
+            .iter()
+            .map(|step| step["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Check out repository",
+                "Configure git",
+                "tag",
+                "publish",
+                "release"
+            ]
+        );
+        let continue_on_error = steps
+            .iter()
+            .map(|step| step.get("continue-on-error").cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            continue_on_error,
+            [None, None, Some(Value::from(true)), None, None]
+        );
+
+        assert_eq!(
+            steps[3]["env"]["CARGO_REGISTRY_TOKEN"],
+            Value::from(format!("${{{{ secrets.{CRATES_IO_TOKEN_SECRET} }}}}"))
+        );
+        assert_eq!(
+            steps[4]["env"]["GH_TOKEN"],
+            Value::from("${{ github.token }}")
+        );
+        assert!(steps[2].get("env").is_none());
+
+        // Steps run the plan's shell commands from the checked out repository,
+        // without changing to the plan's path of it.
+        let runs = steps[2..]
+            .iter()
+            .map(|step| step["run"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                "printf '%s' '# Release\n\n- `it'\\''s` $HOME\n' | git tag --annotate \
+                 --cleanup=whitespace --file - 'x$(id)' || true",
+                "cargo publish --package vm-memory || exit 1",
+                "gh release create 'x$(id)' --latest --notes 'multi\nline' --verify-tag --title \
+                 vm-memory-v1.0.0 || exit 1",
+            ]
+        );
+    }
+}
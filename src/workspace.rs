@@ -0,0 +1,779 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Workspace model built from `cargo metadata` output.
+//!
+//! Cargo resolves globbed members, inherited fields, auto-discovered targets
+//! and dependency kinds for us, so its view of the workspace is the one the
+//! rest of the tool works with. When `cargo` cannot be run at all, the
+//! manifests are read directly instead, which gives the same picture except
+//! for auto-discovered targets and implicit features of optional
+//! dependencies.
+
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use serde::Deserialize as _;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    Context,
+    utilities::repository::{self, DependencyField, Manifest},
+};
+
+/// The members of a cargo workspace.
+#[derive(Clone, PartialEq)]
+pub struct Workspace {
+    /// Directory of the workspace root manifest.
+    pub root: PathBuf,
+    pub members: Vec<Package>,
+}
+
+/// Only lists member names, since workspaces end up in the debug output of
+/// health checks.
+impl std::fmt::Debug for Workspace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Workspace")
+            .field("root", &self.root)
+            .field("members", &self.member_names())
+            .finish()
+    }
+}
+
+/// A workspace member, as reported by `cargo metadata`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// Cargo's package id. Empty when the workspace was not loaded through
+    /// cargo.
+    #[serde(default)]
+    pub id: String,
+    pub manifest_path: PathBuf,
+    #[serde(default)]
+    pub edition: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub readme: Option<String>,
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Registries the package may be published to; `None` means any.
+    #[serde(default)]
+    pub publish: Option<Vec<String>>,
+    #[serde(default)]
+    pub features: IndexMap<String, Vec<String>>,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Package {
+    /// Directory containing the package manifest.
+    pub fn dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(&self.manifest_path)
+    }
+
+    /// Whether `publish` allows publishing the package to crates.io.
+    pub fn is_publishable(&self) -> bool {
+        self.publish
+            .as_ref()
+            .is_none_or(|registries| registries.iter().any(|r| r == "crates-io"))
+    }
+
+    /// Value of `publish` as it would be written in the manifest.
+    pub fn publish_setting(&self) -> String {
+        match &self.publish {
+            None => "true".to_string(),
+            Some(registries) if registries.is_empty() => "false".to_string(),
+            Some(registries) => format!("{registries:?}"),
+        }
+    }
+
+    /// Normal and build dependencies, i.e. everything except
+    /// dev-dependencies, which published crates don't need.
+    pub fn non_dev_dependencies(&self) -> impl Iterator<Item = &Dependency> {
+        self.dependencies
+            .iter()
+            .filter(|d| d.kind != DependencyKind::Development)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<TargetKind>,
+    #[serde(default)]
+    pub crate_types: Vec<String>,
+    pub src_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetKind {
+    Lib,
+    Rlib,
+    Dylib,
+    Cdylib,
+    Staticlib,
+    ProcMacro,
+    Bin,
+    Example,
+    Test,
+    Bench,
+    CustomBuild,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dependency {
+    /// Name of the depended-on package (not the rename, if any).
+    pub name: String,
+    /// Version requirement; `*` if the manifest specifies none.
+    pub req: String,
+    #[serde(default, deserialize_with = "DependencyKind::deserialize_nullable")]
+    pub kind: DependencyKind,
+    /// Name the package is renamed to in the manifest.
+    #[serde(default)]
+    pub rename: Option<String>,
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default = "default_true")]
+    pub uses_default_features: bool,
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Platform (`cfg(...)` or target triple) the dependency is limited to.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Source id for non-path dependencies, e.g. a registry or git URL.
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub registry: Option<String>,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    #[default]
+    Normal,
+    #[serde(rename = "dev")]
+    Development,
+    Build,
+}
+
+impl DependencyKind {
+    /// `cargo metadata` reports normal dependencies with a `null` kind.
+    fn deserialize_nullable<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Ok(Option::<Self>::deserialize(deserializer)?.unwrap_or_default())
+    }
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_members: Vec<String>,
+    workspace_root: PathBuf,
+}
+
+impl Workspace {
+    /// Load the workspace whose root (or single package) manifest is in
+    /// `path`.
+    pub fn load(path: &Path, context: &Context) -> Result<Self, Box<dyn std::error::Error>> {
+        let manifest_path = path.canonicalize()?.join("Cargo.toml");
+        match std::process::Command::new(context.cargo_bin())
+            .args([
+                "metadata",
+                "--format-version",
+                "1",
+                "--no-deps",
+                "--manifest-path",
+            ])
+            .arg(&manifest_path)
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                let metadata: Metadata = serde_json::from_slice(&output.stdout)
+                    .map_err(|err| format!("Could not parse cargo metadata output: {err}"))?;
+                let members = metadata
+                    .packages
+                    .into_iter()
+                    .filter(|p| metadata.workspace_members.contains(&p.id))
+                    .collect();
+                Ok(Self {
+                    root: metadata.workspace_root,
+                    members,
+                })
+            }
+            Ok(output) => Err(format!(
+                "cargo metadata failed for {}: {}",
+                manifest_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::warn!(
+                    "Could not run {:?} ({err}), reading manifests directly",
+                    context.cargo_bin().as_ref()
+                );
+                Self::from_manifests(&manifest_path)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Member named `name`, if any.
+    pub fn package(&self, name: &str) -> Option<&Package> {
+        self.members.iter().find(|p| p.name == name)
+    }
+
+    /// Names of the workspace members.
+    pub fn member_names(&self) -> Vec<&str> {
+        self.members.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Member whose manifest is in directory `dir`.
+    pub fn package_at(&self, dir: &Path) -> Option<&Package> {
+        let dir = dir.canonicalize().ok()?;
+        self.members
+            .iter()
+            .find(|p| p.dir().canonicalize().is_ok_and(|d| d == dir))
+    }
+
+    /// Path dependencies of `package`, along with the current version of the
+    /// package each one points to. Packages outside of the workspace are
+    /// loaded from their own manifests.
+    pub fn path_dependency_versions<'p>(
+        &self,
+        package: &'p Package,
+        context: &Context,
+    ) -> Result<Vec<(&'p Dependency, String)>, Box<dyn std::error::Error>> {
+        let mut versions = vec![];
+        for dependency in &package.dependencies {
+            let Some(path) = &dependency.path else {
+                continue;
+            };
+            let version = match self.package_at(path) {
+                Some(target) => target.version.clone(),
+                None => Self::load(path, context)?
+                    .package_at(path)
+                    .map(|target| target.version.clone())
+                    .ok_or_else(|| format!("No package found at `{}`", path.display()))?,
+            };
+            versions.push((dependency, version));
+        }
+        Ok(versions)
+    }
+
+    /// Fallback for when cargo is unavailable: parse the root manifest and
+    /// every member manifest it lists.
+    fn from_manifests(manifest_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = manifest_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .to_path_buf();
+        let contents = std::fs::read_to_string(manifest_path)?;
+        let value: toml::Value = toml::from_str(&contents)
+            .map_err(|err| format!("Could not parse {}: {err}", manifest_path.display()))?;
+        let workspace = value
+            .get("workspace")
+            .cloned()
+            .map(toml::Value::try_into::<repository::Workspace>)
+            .transpose()
+            .map_err(|err| format!("Could not parse {}: {err}", manifest_path.display()))?;
+        let mut manifest_paths = vec![];
+        if value.get("package").is_some() {
+            manifest_paths.push(manifest_path.to_path_buf());
+        }
+        let members = value
+            .get("workspace")
+            .and_then(|w| w.get("members"))
+            .and_then(toml::Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for member in members {
+            let Some(member) = member.as_str() else {
+                return Err(format!(
+                    "{}: workspace member {member} is not a string",
+                    manifest_path.display()
+                )
+                .into());
+            };
+            for dir in expand_member(&root, member)? {
+                let member_manifest = dir.join("Cargo.toml");
+                if !manifest_paths.contains(&member_manifest) {
+                    manifest_paths.push(member_manifest);
+                }
+            }
+        }
+        let members = manifest_paths
+            .into_iter()
+            .map(|path| Package::from_manifest(path, &root, workspace.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { root, members })
+    }
+}
+
+impl Package {
+    /// Read the package manifest at `manifest_path`, resolving fields and
+    /// dependencies inherited from `workspace`, whose root is `root`.
+    fn from_manifest(
+        manifest_path: PathBuf,
+        root: &Path,
+        workspace: Option<&repository::Workspace>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .map_err(<Box<dyn std::error::Error>>::from)
+            .and_then(|m| toml::from_str::<Manifest>(&m).map_err(|err| err.into()))
+            .map_err(|err| format!("Could not parse {}: {err}", manifest_path.display()))?;
+        let with_path = |err: Box<dyn std::error::Error>| -> Box<dyn std::error::Error> {
+            format!("{}: {err}", manifest_path.display()).into()
+        };
+        let dir = manifest_path.parent().unwrap_or(Path::new("."));
+        let tables = [(
+            None,
+            &manifest.dependencies,
+            &manifest.dev_dependencies,
+            &manifest.build_dependencies,
+        )]
+        .into_iter()
+        .chain(manifest.target.iter().map(|(target, t)| {
+            (
+                Some(target),
+                &t.dependencies,
+                &t.dev_dependencies,
+                &t.build_dependencies,
+            )
+        }))
+        .flat_map(|(target, normal, dev, build)| {
+            [
+                (target, DependencyKind::Normal, normal),
+                (target, DependencyKind::Development, dev),
+                (target, DependencyKind::Build, build),
+            ]
+        });
+        let mut dependencies = vec![];
+        for (target, kind, table) in tables {
+            for (key, field) in table {
+                let mut dependency =
+                    Dependency::resolve(key, field, dir, root, workspace).map_err(with_path)?;
+                dependency.kind = kind;
+                dependency.target = target.cloned();
+                dependencies.push(dependency);
+            }
+        }
+        let package = manifest
+            .package
+            .resolve(workspace.map(|w| &w.package))
+            .map_err(with_path)?;
+        Ok(Self {
+            name: manifest.package.name,
+            version: package.version.unwrap_or_default(),
+            id: String::new(),
+            manifest_path,
+            edition: package.edition,
+            authors: package.authors.unwrap_or_default(),
+            description: package.description,
+            repository: package.repository,
+            readme: package.readme,
+            license: package.license,
+            publish: package.publish.unwrap_or_default().registries(),
+            features: manifest.features,
+            targets: vec![],
+            dependencies,
+        })
+    }
+}
+
+impl Dependency {
+    /// Key the dependency is declared under in the manifest.
+    pub fn key(&self) -> &str {
+        self.rename.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Version requirement, unless the manifest specifies none.
+    pub fn version_req(&self) -> Option<&str> {
+        Some(self.req.as_str()).filter(|req| *req != "*")
+    }
+
+    /// Manifest table the dependency is declared in, e.g. `dependencies` or
+    /// `target.'cfg(unix)'.dev-dependencies`.
+    pub fn table(&self) -> String {
+        let kind = match self.kind {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Development => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        };
+        match &self.target {
+            Some(target) => format!("target.'{target}'.{kind}"),
+            None => kind.to_string(),
+        }
+    }
+
+    /// Dependency declared as `field` under `key` in the manifest in `dir`.
+    /// Inherited dependencies are looked up in `workspace`, whose root is
+    /// `root`. The kind and target are left for the caller to fill in.
+    fn resolve(
+        key: &str,
+        field: &DependencyField,
+        dir: &Path,
+        root: &Path,
+        workspace: Option<&repository::Workspace>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let d = match field {
+            DependencyField::Version(version) => {
+                return Ok(Self {
+                    name: key.to_string(),
+                    req: version.clone(),
+                    kind: DependencyKind::Normal,
+                    rename: None,
+                    optional: false,
+                    uses_default_features: true,
+                    features: vec![],
+                    target: None,
+                    source: Some(
+                        "registry+https://github.com/rust-lang/crates.io-index".to_string(),
+                    ),
+                    registry: None,
+                    path: None,
+                });
+            }
+            DependencyField::Dependency(d) => d,
+            DependencyField::Inherited(inherited) => {
+                if !inherited.workspace {
+                    return Err(format!("`{key}.workspace` cannot be false").into());
+                }
+                let Some(field) = workspace.and_then(|w| w.dependencies.get(key)) else {
+                    return Err(format!(
+                        "`{key}` is inherited but not in [workspace.dependencies]"
+                    )
+                    .into());
+                };
+                if matches!(field, DependencyField::Inherited(_)) {
+                    return Err(
+                        format!("`{key}` in [workspace.dependencies] cannot be inherited").into(),
+                    );
+                }
+                let mut resolved = Self::resolve(key, field, root, root, None)?;
+                resolved.features.extend(inherited.features.iter().cloned());
+                resolved.optional = inherited.optional;
+                return Ok(resolved);
+            }
+        };
+        Ok(Self {
+            name: d.package.clone().unwrap_or_else(|| key.to_string()),
+            req: d.version.clone().unwrap_or_else(|| "*".to_string()),
+            kind: DependencyKind::Normal,
+            rename: d.package.is_some().then(|| key.to_string()),
+            optional: d.optional,
+            uses_default_features: d.default_features,
+            features: d.features.clone(),
+            target: None,
+            source: d.source(),
+            registry: d.registry.clone(),
+            path: d.path.as_ref().map(|p| dir.join(p)),
+        })
+    }
+}
+
+/// Directories matching the workspace member `pattern`, which may contain
+/// `*` and `?` wildcards in any path component.
+fn expand_member(root: &Path, pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut dirs = vec![root.to_path_buf()];
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains(['*', '?']) {
+            dirs.iter_mut().for_each(|d| d.push(&*component));
+            continue;
+        }
+        let mut matches = vec![];
+        for dir in dirs {
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir()
+                    && wildcard_match(&component, &entry.file_name().to_string_lossy())
+                {
+                    matches.push(entry.path());
+                }
+            }
+        }
+        matches.sort();
+        dirs = matches;
+    }
+    // Like cargo, globs only pick up directories that contain a package.
+    if pattern.contains(['*', '?']) {
+        dirs.retain(|d| d.join("Cargo.toml").is_file());
+    }
+    Ok(dirs)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.chars().next() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            name.char_indices()
+                .map(|(i, _)| i)
+                .chain([name.len()])
+                .any(|i| wildcard_match(rest, &name[i..]))
+        }
+        Some(p) => {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(n) if p == '?' || p == n => {
+                    wildcard_match(&pattern[p.len_utf8()..], chars.as_str())
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::write_files;
+
+    /// Workspace with a root package, globbed members inheriting from
+    /// `[workspace.package]` and `[workspace.dependencies]`, and directories
+    /// the glob matches that are not packages.
+    fn fixture(root: &Path) {
+        write_files(
+            root,
+            &[
+                (
+                    "Cargo.toml",
+                    r#"[package]
+name = "vm-root"
+version = "0.3.0"
+edition = "2021"
+
+[workspace]
+members = ["crates/*", "tools/vm-tool"]
+
+[workspace.package]
+version = "0.2.0"
+edition = "2024"
+license = "Apache-2.0 OR BSD-3-Clause"
+publish = false
+
+[workspace.dependencies]
+serde = { version = "1.0", default-features = false }
+vm-a = { path = "crates/vm-a", version = "0.2.0" }
+"#,
+                ),
+                (
+                    "crates/vm-a/Cargo.toml",
+                    r#"[package]
+name = "vm-a"
+version.workspace = true
+edition = { workspace = true }
+license.workspace = true
+
+[dependencies]
+serde = { workspace = true, features = ["derive"] }
+"#,
+                ),
+                (
+                    "crates/vm-b/Cargo.toml",
+                    r#"[package]
+name = "vm-b"
+version = "1.0.0"
+publish.workspace = true
+
+[dependencies]
+vm-a = { workspace = true, features = ["std"], optional = true }
+
+[target.'cfg(unix)'.dev-dependencies]
+renamed = { package = "vm-a", path = "../vm-a" }
+"#,
+                ),
+                ("crates/docs/README.md", "Not a package.\n"),
+                ("crates/README.md", "Not a directory.\n"),
+                (
+                    "tools/vm-tool/Cargo.toml",
+                    "[package]\nname = \"vm-tool\"\nversion = \"0.1.0\"\n",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_from_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fixture(root);
+        let workspace = Workspace::from_manifests(&root.join("Cargo.toml")).unwrap();
+        assert_eq!(workspace.root, root);
+        assert_eq!(
+            workspace.member_names(),
+            ["vm-root", "vm-a", "vm-b", "vm-tool"]
+        );
+
+        // Fields set in the package take precedence over the workspace ones.
+        let root_package = workspace.package("vm-root").unwrap();
+        assert_eq!(root_package.version, "0.3.0");
+        assert_eq!(root_package.edition.as_deref(), Some("2021"));
+        assert!(root_package.is_publishable());
+
+        let a = workspace.package("vm-a").unwrap();
+        assert_eq!(a.manifest_path, root.join("crates/vm-a/Cargo.toml"));
+        assert_eq!(a.version, "0.2.0");
+        assert_eq!(a.edition.as_deref(), Some("2024"));
+        assert_eq!(a.license.as_deref(), Some("Apache-2.0 OR BSD-3-Clause"));
+        // Fields that are not inherited are not set.
+        assert_eq!(a.publish, None);
+        let [serde] = a.dependencies.as_slice() else {
+            panic!("{:?}", a.dependencies);
+        };
+        assert_eq!(serde.req, "1.0");
+        assert!(!serde.uses_default_features);
+        assert_eq!(serde.features, ["derive"]);
+        assert_eq!(
+            serde.source.as_deref(),
+            Some("registry+https://github.com/rust-lang/crates.io-index")
+        );
+
+        let b = workspace.package("vm-b").unwrap();
+        assert_eq!(b.version, "1.0.0");
+        assert!(!b.is_publishable());
+        let [inherited, renamed] = b.dependencies.as_slice() else {
+            panic!("{:?}", b.dependencies);
+        };
+        // Inherited paths are relative to the workspace root.
+        assert_eq!(inherited.name, "vm-a");
+        assert_eq!(inherited.req, "0.2.0");
+        assert_eq!(inherited.path, Some(root.join("crates/vm-a")));
+        assert_eq!(inherited.features, ["std"]);
+        assert!(inherited.optional);
+        assert_eq!(inherited.table(), "dependencies");
+        assert_eq!(renamed.name, "vm-a");
+        assert_eq!(renamed.key(), "renamed");
+        assert_eq!(renamed.version_req(), None);
+        assert_eq!(renamed.path, Some(root.join("crates/vm-b/../vm-a")));
+        assert_eq!(renamed.table(), "target.'cfg(unix)'.dev-dependencies");
+        assert_eq!(b.non_dev_dependencies().count(), 1);
+    }
+
+    #[test]
+    fn test_from_manifests_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fixture(root);
+        let load_error = |member: &str, contents: &str| {
+            write_files(root, &[(member, contents)]);
+            let err = Workspace::from_manifests(&root.join("Cargo.toml"))
+                .unwrap_err()
+                .to_string();
+            std::fs::remove_file(root.join(member)).unwrap();
+            err
+        };
+        let manifest = root.join("crates/vm-c/Cargo.toml");
+
+        let err = load_error(
+            "crates/vm-c/Cargo.toml",
+            "[package]\nname = \"vm-c\"\nversion = \"0.1.0\"\nreadme.workspace = true\n",
+        );
+        assert_eq!(
+            err,
+            format!(
+                "{}: `readme` is inherited but not set in [workspace.package]",
+                manifest.display()
+            )
+        );
+        let err = load_error(
+            "crates/vm-c/Cargo.toml",
+            "[package]\nname = \"vm-c\"\nversion = \"0.1.0\"\n\n[dependencies]\nlibc.workspace = \
+             true\n",
+        );
+        assert_eq!(
+            err,
+            format!(
+                "{}: `libc` is inherited but not in [workspace.dependencies]",
+                manifest.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fixture(root);
+        write_files(
+            root,
+            &[
+                ("crates/vm-a/fuzz/Cargo.toml", ""),
+                ("crates/vm-b/fuzz/Cargo.toml", ""),
+            ],
+        );
+
+        assert_eq!(
+            expand_member(root, "crates/*").unwrap(),
+            [root.join("crates/vm-a"), root.join("crates/vm-b")]
+        );
+        assert_eq!(
+            expand_member(root, "crates/vm-?").unwrap(),
+            [root.join("crates/vm-a"), root.join("crates/vm-b")]
+        );
+        assert_eq!(
+            expand_member(root, "crates/*/fuzz").unwrap(),
+            [root.join("crates/vm-a/fuzz"), root.join("crates/vm-b/fuzz")]
+        );
+        assert_eq!(
+            expand_member(root, "*/vm-t*").unwrap(),
+            [root.join("tools/vm-tool")]
+        );
+        assert!(expand_member(root, "crates/x*").unwrap().is_empty());
+        // Members without wildcards are taken as they are.
+        assert_eq!(
+            expand_member(root, "crates/docs").unwrap(),
+            [root.join("crates/docs")]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("vm-*", "vm-memory"));
+        assert!(wildcard_match("*-sys", "kvm-bindings-sys"));
+        assert!(wildcard_match("v?-*y", "vm-memory"));
+        assert!(wildcard_match("vm-ü*", "vm-über"));
+        assert!(!wildcard_match("vm-*", "kvm-ioctls"));
+        assert!(!wildcard_match("vm-?", "vm-"));
+        assert!(!wildcard_match("vm", "vm-memory"));
+    }
+}
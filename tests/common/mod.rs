@@ -0,0 +1,75 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Helpers shared by the integration tests and, as `crate::test_utils`, by
+//! the unit tests.
+
+// Every test crate uses a different subset of the helpers.
+#![allow(dead_code)]
+
+use std::path::Path;
+
+/// Run `git` in `repository`, returning its standard output. Panics if it
+/// fails.
+pub fn git(repository: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(repository)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Write `files`, given as path relative to `root` and contents, creating
+/// missing directories.
+pub fn write_files(root: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+}
+
+/// Write an executable script to `path`, e.g. a stub of a command.
+pub fn write_executable(path: &Path, contents: &str) {
+    std::fs::write(path, contents).unwrap();
+    std::fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+}
+
+/// Create a repository in `repository`, checked out on `branch`, with an
+/// identity to commit as and a first commit adding `files`.
+pub fn repository(repository: &Path, branch: &str, files: &[(&str, &str)]) {
+    std::fs::create_dir_all(repository).unwrap();
+    git(
+        repository,
+        &["init", "--quiet", &format!("--initial-branch={branch}")],
+    );
+    git(repository, &["config", "user.name", "Test"]);
+    git(repository, &["config", "user.email", "test@example.com"]);
+    write_files(repository, files);
+    git(repository, &["add", "."]);
+    git(
+        repository,
+        &["commit", "--quiet", "--message", "Initial commit"],
+    );
+}
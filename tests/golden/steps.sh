@@ -0,0 +1,54 @@
+#!/bin/sh
+
+set -e
+
+cd 'it'\''s a "repo"' || exit 1
+[ "$(gh repo view --json defaultBranchRef --jq .defaultBranchRef.name)" = "$(git rev-parse --abbrev-ref HEAD)" ] || { echo 'Currently checked out branch is not the default branch, please check it out.' >&2; exit 1; }
+printf '%s' '# Release `x$(id)`
+
+It'\''s "quoted", 100% $HOME \n
+- %s
+' | git tag --annotate --cleanup=whitespace --file - 'x$(id)' || exit 1
+printf '%s' '# Release `x$(id)`
+
+It'\''s "quoted", 100% $HOME \n
+- %s
+' | git tag --annotate --cleanup=whitespace --file - v1.0.0 || exit 1
+git checkout -b 'release-x$(id)' master || exit 1
+# edit Cargo.toml
+cat > Cargo.toml <<'RUST_VMM_HELPER_CLI_EOF' || exit 1
+[package]
+name = "vm-fixture"
+version = "1.0.0"
+RUST_VMM_HELPER_CLI_EOF
+# edit CHANGELOG.md
+cat > CHANGELOG.md <<'RUST_VMM_HELPER_CLI_EOF' || exit 1
+# Changelog
+
+## [Unreleased]
+
+## [v1.0.0] - 2025-01-01
+
+### Added
+
+- `x$(id)`
+RUST_VMM_HELPER_CLI_EOF
+git add Cargo.toml CHANGELOG.md || exit 1
+git commit --signoff --message 'Bump vm-fixture to v1.0.0' || exit 1
+git push --set-upstream upstream 'release-x$(id)' || exit 1
+gh pr create --title 'Bump vm-fixture to v1.0.0' --body '# Release `x$(id)`
+
+It'\''s "quoted", 100% $HOME \n
+- %s
+' --head 'me:release-x$(id)' --assignee @me || exit 1
+gh release create 'x$(id)' --latest --notes '# Release `x$(id)`
+
+It'\''s "quoted", 100% $HOME \n
+- %s
+' --verify-tag --title vm-fixture-v1.0.0 || true
+lines=$(gh pr list --state open --search '"Bump vm-fixture to v1.0.0" in:title' --json number --jq '.[].number') || true
+for line in $lines; do gh pr close "$line" || true; done
+cargo publish --dry-run --package vm-fixture || exit 1
+deadline=$(($(date +%s) + 10)); delay=5
+until curl --silent --fail --user-agent rust-vmm-helper-cli https://crates.io/api/v1/crates/vm-fixture/1.0.0 >/dev/null; do left=$((deadline - $(date +%s))); [ "$left" -gt 0 ] || { echo 'Timed out waiting for `vm-fixture` v1.0.0 on crates.io' >&2; exit 1; }; sleep $((delay < left ? delay : left)); delay=$((delay * 2)); [ "$delay" -le 60 ] || delay=60; done
+git tag --delete 'x$(id)' || true
@@ -0,0 +1,299 @@
+//
+// rust-vmm-helper-cli
+//
+// Copyright 2025 Manos Pitsidianakis <manos.pitsidianakis@linaro.org>
+//
+// This file is part of rust-vmm-helper-cli.
+//
+// rust-vmm-helper-cli is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// rust-vmm-helper-cli is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with rust-vmm-helper-cli. If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: EUPL-1.2 OR GPL-3.0-or-later
+
+//! Golden tests of the shell commands that steps are exported as.
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the files in `tests/golden` after an
+//! intended change, and review their diff.
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use rust_vmm_helper_cli::{
+    Context,
+    actions::{Action, CratesIOAction, GithubAction, LocalAction},
+    steps::{EditedFiles, Step},
+    utilities::shell,
+};
+
+/// Repository directory, relative to the script's working directory.
+const REPOSITORY: &str = "it's a \"repo\"";
+const TAG: &str = "x$(id)";
+/// Tag message and release notes.
+const DESCRIPTION: &str = "# Release `x$(id)`\n\nIt's \"quoted\", 100% $HOME \\n\n- %s\n";
+
+/// Stub of `cargo`, `curl`, `gh` and `git` that logs its arguments and
+/// standard input to `$STUB_LOG`.
+const STUB: &str = r#"#!/bin/sh
+name=$(basename "$0")
+{
+    printf '%s' "$name"
+    for arg in "$@"; do
+        printf ' [%s]' "$arg"
+    done
+    printf '\n'
+    input=$(cat)
+    [ -z "$input" ] || printf 'stdin [%s]\n' "$input"
+} >> "$STUB_LOG"
+case "$name $1 $2" in
+    "gh repo view" | "git rev-parse --abbrev-ref") echo master ;;
+    "gh pr list") printf '12\n13\n' ;;
+esac
+"#;
+
+const MANIFEST: &str = "[package]\nname = \"vm-fixture\"\nversion = \"0.1.0\"\n";
+const CHANGELOG: &str = "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- `x$(id)`\n";
+
+/// Compare `actual` with golden file `name`, or update it.
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Could not read `{}`: {err}", path.display()));
+    assert!(
+        actual == expected,
+        "`{}` is out of date, rerun with UPDATE_GOLDEN=1 and review the diff.\n--- \
+         expected\n{expected}\n--- actual\n{actual}",
+        path.display()
+    );
+}
+
+/// Actions covering every kind of step, and whether they continue on error.
+fn actions(repository: &Path) -> Vec<(Action, bool)> {
+    let local = |inner| Action::Local {
+        repository: repository.to_path_buf(),
+        inner,
+    };
+    let github = |inner| Action::Github {
+        repository: repository.to_path_buf(),
+        inner,
+    };
+    let crates_io = |inner| Action::CratesIO {
+        repository: repository.to_path_buf(),
+        inner,
+    };
+    vec![
+        (local(LocalAction::AssertDefaultBranch), false),
+        (
+            local(LocalAction::CreateTags {
+                new_tags: vec![TAG.into(), "v1.0.0".into()],
+                message: Some(DESCRIPTION.into()),
+            }),
+            false,
+        ),
+        (
+            local(LocalAction::CreateBranch {
+                branch: format!("release-{TAG}"),
+                base: "master".into(),
+            }),
+            false,
+        ),
+        (
+            local(LocalAction::CommitVersionBump {
+                crate_name: "vm-fixture".into(),
+                new_version: "1.0.0".into(),
+                manifest: "Cargo.toml".into(),
+                workspace_version: false,
+                changelog: Some("CHANGELOG.md".into()),
+                date: "2025-01-01".into(),
+            }),
+            false,
+        ),
+        (
+            local(LocalAction::PushBranch {
+                branch: format!("release-{TAG}"),
+                remote: "upstream".into(),
+            }),
+            false,
+        ),
+        (
+            github(GithubAction::CreateReleasePR {
+                crate_name: "vm-fixture".into(),
+                new_version: "1.0.0".into(),
+                body: Some(DESCRIPTION.into()),
+                head: Some(format!("me:release-{TAG}")),
+            }),
+            false,
+        ),
+        (
+            github(GithubAction::CreateRelease {
+                crate_name: "vm-fixture".into(),
+                tag: TAG.into(),
+                version: "1.0.0".into(),
+                notes: Some(DESCRIPTION.into()),
+            }),
+            true,
+        ),
+        (
+            github(GithubAction::CloseReleasePR {
+                crate_name: "vm-fixture".into(),
+                new_version: "1.0.0".into(),
+            }),
+            true,
+        ),
+        (
+            crates_io(CratesIOAction::Publish {
+                crate_name: "vm-fixture".into(),
+                dry_run: true,
+            }),
+            false,
+        ),
+        (
+            crates_io(CratesIOAction::WaitForVersion {
+                crate_name: "vm-fixture".into(),
+                version: "1.0.0".into(),
+                timeout: 10,
+            }),
+            false,
+        ),
+        (
+            local(LocalAction::DeleteTags {
+                tags: vec![TAG.into()],
+            }),
+            true,
+        ),
+    ]
+}
+
+/// Directory with the stubs in `bin` and the repository files the steps
+/// edit.
+fn fixture() -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    for name in ["cargo", "curl", "gh", "git"] {
+        common::write_executable(&bin.join(name), STUB);
+    }
+    let repository = dir.path().join(REPOSITORY);
+    common::write_files(
+        &repository,
+        &[("Cargo.toml", MANIFEST), ("CHANGELOG.md", CHANGELOG)],
+    );
+    (dir, repository)
+}
+
+#[test]
+fn test_steps_golden() {
+    let (dir, repository) = fixture();
+    let mut context = Context::new();
+
+    let mut script = String::from("#!/bin/sh\n\nset -e\n\n");
+    script += &shell::cd(Path::new(REPOSITORY)).unwrap();
+    script.push('\n');
+    let mut edited_files = EditedFiles::new();
+    for (action, continue_on_error) in actions(&repository) {
+        for step in action.steps(&mut context) {
+            if let Step::EditFile { ref path, .. } = step {
+                script += &format!("# edit {}\n", path.display());
+            }
+            for line in step
+                .as_shell_command(&repository, continue_on_error, &mut edited_files)
+                .unwrap()
+            {
+                script += &line;
+                script.push('\n');
+            }
+        }
+    }
+    assert_golden("steps.sh", &script);
+
+    let script_path = dir.path().join("steps.sh");
+    std::fs::write(&script_path, &script).unwrap();
+    let syntax = Command::new("sh")
+        .arg("-n")
+        .arg(&script_path)
+        .output()
+        .unwrap();
+    assert!(syntax.status.success(), "{syntax:?}");
+
+    let log = dir.path().join("stub.log");
+    let path = std::env::join_paths(std::iter::once(dir.path().join("bin")).chain(
+        std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+    ))
+    .unwrap();
+    let output = Command::new("sh")
+        .arg(&script_path)
+        .current_dir(dir.path())
+        .env("PATH", path)
+        .env("STUB_LOG", &log)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_golden("steps.log", &std::fs::read_to_string(&log).unwrap());
+
+    assert!(
+        std::fs::read_to_string(repository.join("Cargo.toml"))
+            .unwrap()
+            .contains("version = \"1.0.0\"")
+    );
+    assert!(
+        std::fs::read_to_string(repository.join("CHANGELOG.md"))
+            .unwrap()
+            .contains("## [v1.0.0] - 2025-01-01")
+    );
+}
+
+#[test]
+fn test_quote_round_trip() {
+    let args = [
+        "",
+        "plain-word_1.0+b=c@d%e",
+        "two words",
+        TAG,
+        "it's",
+        "''",
+        "\"$HOME\"",
+        "`id`",
+        "a\\nb",
+        "multi\nline\n",
+        "-n",
+        "*",
+        "~",
+        "é",
+        DESCRIPTION,
+    ];
+    let mut golden = String::new();
+    for arg in args {
+        let quoted = shell::quote(arg);
+        golden += &format!("{quoted}\n");
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s' {quoted}"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), arg);
+    }
+    golden += &shell::join(&args);
+    golden.push('\n');
+    assert_golden("quote.txt", &golden);
+}